@@ -19,11 +19,15 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+use std::sync::RwLock;
 use int_enum::IntEnum;
 
 /// Type represents direction of the move.
 #[repr(u8)]
 #[derive(PartialEq,Eq,Debug,Clone,Copy,IntEnum)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize,serde::Deserialize))]
 pub enum Direction {
     /// Move left.
     Left = 0,
@@ -43,6 +47,74 @@ pub enum Direction {
     PushDown = 7,
     /// No direction.
     NoDirection = 8,
+    /// Move left and pull pack from right.
+    PullLeft = 9,
+    /// Move right and pull pack from left.
+    PullRight = 10,
+    /// Move up and pull pack from down.
+    PullUp = 11,
+    /// Move down and pull pack from up.
+    PullDown = 12,
+}
+
+/// Reason why move is blocked.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum BlockedReason {
+    /// Move beyond edge of level area.
+    Edge,
+    /// Wall in direction of move.
+    Wall,
+    /// Pack can not be pushed - wall or edge behind pack.
+    BoxAgainstWall,
+    /// Pack can not be pushed - other pack behind pack.
+    BoxAgainstBox,
+    /// Direction is not supported by move - no direction or pull direction.
+    Unsupported,
+}
+
+/// Rules of game.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Rules {
+    /// Maximal number of packs in a row that can be pushed by one move (at least 1).
+    /// Standard Sokoban allows to push one pack.
+    pub push_chain: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules{ push_chain: 1 }
+    }
+}
+
+/// Outcome of move - direction of done move (push direction if move pushes pack)
+/// or reason why move is blocked.
+pub type MoveOutcome = Result<Direction, BlockedReason>;
+
+/// Event produced by change of level state.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum StateEvent {
+    /// Player moved in direction (push direction if move pushes pack).
+    Moved(Direction),
+    /// Pack pushed from position to position.
+    PackPushed{ from: (usize, usize), to: (usize, usize) },
+    /// Pack placed on target at position.
+    PackOnTarget(usize, usize),
+    /// Pack removed from target at position.
+    PackOffTarget(usize, usize),
+    /// Level has been solved.
+    Solved,
+}
+
+/// Result of single step in game: outcome of move, produced events
+/// and whether level is solved after step.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct StepResult {
+    /// Outcome of move.
+    pub outcome: MoveOutcome,
+    /// Events produced by move.
+    pub events: Vec<StateEvent>,
+    /// True if level is solved after step.
+    pub solved: bool,
 }
 
 /// Type represents field in level area.
@@ -88,9 +160,20 @@ pub enum CheckError {
     LockedPackApartWalls(usize, usize),
     /// If walls and packs creates 2x2 block - place of 2x2 block.
     Locked2x2Block(usize, usize),
+    /// If level is smaller than 3x3 or size of area doesn't match dimensions -
+    /// width and height of level.
+    DegenerateSize(usize, usize),
+}
+
+#[derive(Debug,PartialEq,Eq,Copy,Clone)]
+/// Check level warning - informational, level with warnings is valid.
+pub enum CheckWarning {
+    /// If pack is on target at start of level - place of pack.
+    PrefilledBox(usize, usize),
 }
 
 #[derive(PartialEq,Eq,Debug,Copy,Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize,serde::Deserialize))]
 /// Error caused while parsing or creating level.
 pub enum ParseError {
     /// If empty lines.
@@ -108,12 +191,190 @@ pub enum XmlParseError {
     BadStructure,
 }
 
+/// Parse error concerned binary level set format.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum BinaryParseError {
+    /// If bad magic bytes.
+    BadMagic,
+    /// If bad content of binary data.
+    BadContent,
+}
+
+/// Error caused while editing level.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum EditError {
+    /// If position (x, y) is outside level.
+    OutOfRange(usize, usize),
+}
+
+/// Characters of fields in text level format. Every field can be denoted by
+/// any character from its string. Default charset contains only standard characters.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct FieldCharset {
+    /// Characters of empty field.
+    pub empty: String,
+    /// Characters of wall.
+    pub wall: String,
+    /// Characters of player.
+    pub player: String,
+    /// Characters of player on target.
+    pub player_on_target: String,
+    /// Characters of target.
+    pub target: String,
+    /// Characters of pack.
+    pub pack: String,
+    /// Characters of pack on target.
+    pub pack_on_target: String,
+}
+
+/// Error caused while reading levelset.
+#[derive(Debug)]
+pub enum LevelSetError {
+    /// Input/output error.
+    Io(io::Error),
+    /// Bad structure of XML document.
+    XmlParse(XmlParseError),
+    /// Error from XML reader.
+    Xml(quick_xml::Error),
+    /// Error while parsing integer (for example dimensions of level).
+    ParseInt(ParseIntError),
+    /// Error while parsing binary levelset.
+    BinaryParse(BinaryParseError),
+    /// Unsupported format of levelset - name of format.
+    UnsupportedFormat(String),
+    /// Levelset doesn't contain any level.
+    EmptyLevelSet,
+    /// Error while reading levelset in JSON format.
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+use Direction::*;
 use Field::*;
 use CheckError::*;
 use ParseError::*;
 use XmlParseError::*;
+use BinaryParseError::*;
+use EditError::*;
+
+impl Direction {
+    /// Four base moves (without pushes) in order: left, right, up, down.
+    pub const MOVES: [Direction; 4] = [Left, Right, Up, Down];
+    
+    /// Return iterator over four base moves (without pushes).
+    pub fn all_moves() -> impl Iterator<Item=Direction> {
+        Self::MOVES.iter().copied()
+    }
+    
+    /// Return opposite direction (push direction for push, pull direction for pull).
+    /// NoDirection for NoDirection.
+    pub fn opposite(self) -> Direction {
+        match self {
+            Left => Right,
+            Right => Left,
+            Up => Down,
+            Down => Up,
+            PushLeft => PushRight,
+            PushRight => PushLeft,
+            PushUp => PushDown,
+            PushDown => PushUp,
+            PullLeft => PullRight,
+            PullRight => PullLeft,
+            PullUp => PullDown,
+            PullDown => PullUp,
+            NoDirection => NoDirection,
+        }
+    }
+    
+    /// Return true if direction is push.
+    pub fn is_push(self) -> bool {
+        matches!(self, PushLeft|PushRight|PushUp|PushDown)
+    }
+    
+    /// Return true if direction is pull.
+    pub fn is_pull(self) -> bool {
+        matches!(self, PullLeft|PullRight|PullUp|PullDown)
+    }
+    
+    /// Return move direction without push or pull (for example Left for PushLeft).
+    pub fn as_move(self) -> Direction {
+        match self {
+            PushLeft|PullLeft => Left,
+            PushRight|PullRight => Right,
+            PushUp|PullUp => Up,
+            PushDown|PullDown => Down,
+            d => d,
+        }
+    }
+    
+    /// Parse direction from LURD character: 'l', 'u', 'r', 'd' - moves,
+    /// 'L', 'U', 'R', 'D' - pushes.
+    pub fn from_lurd(c: char) -> Option<Direction> {
+        match c {
+            'l' => Some(Left),
+            'r' => Some(Right),
+            'u' => Some(Up),
+            'd' => Some(Down),
+            'L' => Some(PushLeft),
+            'R' => Some(PushRight),
+            'U' => Some(PushUp),
+            'D' => Some(PushDown),
+            _ => None,
+        }
+    }
+    
+    /// Return LURD character of direction or None if no direction or pull
+    /// (LURD format has no pulls).
+    pub fn to_lurd(self) -> Option<char> {
+        match self {
+            Left => Some('l'),
+            Right => Some('r'),
+            Up => Some('u'),
+            Down => Some('d'),
+            PushLeft => Some('L'),
+            PushRight => Some('R'),
+            PushUp => Some('U'),
+            PushDown => Some('D'),
+            _ => None,
+        }
+    }
+    
+    /// Parse direction from name (case-insensitive): "left", "right", "up", "down",
+    /// "pushleft", "pushright", "pushup", "pushdown", "pullleft", "pullright",
+    /// "pullup", "pulldown" or "nodirection".
+    pub fn from_name(s: &str) -> Option<Direction> {
+        match s.to_ascii_lowercase().as_str() {
+            "left" => Some(Left),
+            "right" => Some(Right),
+            "up" => Some(Up),
+            "down" => Some(Down),
+            "pushleft" => Some(PushLeft),
+            "pushright" => Some(PushRight),
+            "pushup" => Some(PushUp),
+            "pushdown" => Some(PushDown),
+            "pullleft" => Some(PullLeft),
+            "pullright" => Some(PullRight),
+            "pullup" => Some(PullUp),
+            "pulldown" => Some(PullDown),
+            "nodirection" => Some(NoDirection),
+            _ => None,
+        }
+    }
+}
 
 impl Field {
+    /// Return character of field in text format.
+    pub fn to_char(self) -> char {
+        match self {
+            Empty => ' ',
+            Wall => '#',
+            Player => '@',
+            PlayerOnTarget => '+',
+            Target => '.',
+            Pack => '$',
+            PackOnTarget => '*',
+        }
+    }
     /// Return true if is player in this field.
     pub fn is_player(self) -> bool {
         self == Player || self == PlayerOnTarget
@@ -158,20 +419,59 @@ impl Field {
     }
 }
 
+/// Formatter of check error - returns localized message or None if default message
+/// should be used.
+pub type CheckErrorFormatter = fn(&CheckError) -> Option<String>;
+
+/// Formatter of parse error - returns localized message or None if default message
+/// should be used.
+pub type ParseErrorFormatter = fn(&ParseError) -> Option<String>;
+
+static CHECK_ERROR_FORMATTER: RwLock<Option<CheckErrorFormatter>> = RwLock::new(None);
+static PARSE_ERROR_FORMATTER: RwLock<Option<ParseErrorFormatter>> = RwLock::new(None);
+
+/// Set formatter of check errors used by Display. None restores default
+/// English messages.
+pub fn set_check_error_formatter(formatter: Option<CheckErrorFormatter>) {
+    *CHECK_ERROR_FORMATTER.write().unwrap() = formatter;
+}
+
+/// Set formatter of parse errors used by Display. None restores default
+/// English messages.
+pub fn set_parse_error_formatter(formatter: Option<ParseErrorFormatter>) {
+    *PARSE_ERROR_FORMATTER.write().unwrap() = formatter;
+}
+
+impl CheckError {
+    /// Return default English message of error.
+    pub fn default_message(&self) -> String {
+        match self {
+            NoPlayer => "No player".to_string(),
+            TooManyPlayers => "Too many players".to_string(),
+            NoPacksAndTargets => "No packs and targets".to_string(),
+            LevelOpen => "Level open".to_string(),
+            TooFewPacks(x) => format!("Too few packs - required {}", x),
+            TooFewTargets(x) => format!("Too few targets - required {}", x),
+            PackNotAvailable(x, y) => format!("Pack {}x{} not available", x, y),
+            TargetNotAvailable(x, y) => format!("Target {}x{} not available", x, y),
+            LockedPackApartWalls(x, y) => format!("Locked pack {}x{} apart walls", x, y),
+            Locked2x2Block(x, y) => format!("Locked 2x2 block {}x{}", x, y),
+            DegenerateSize(w, h) => format!("Degenerate size {}x{}", w, h),
+        }
+    }
+    
+    /// Return message of error given by formatter or default message if formatter
+    /// returns None.
+    pub fn format_with(&self, formatter: CheckErrorFormatter) -> String {
+        formatter(self).unwrap_or_else(|| self.default_message())
+    }
+}
+
 impl fmt::Display for CheckError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            NoPlayer => write!(f, "No player"),
-            TooManyPlayers => write!(f, "Too many players"),
-            NoPacksAndTargets => write!(f, "No packs and targets"),
-            LevelOpen => write!(f, "Level open"),
-            TooFewPacks(x) => write!(f, "Too few packs - required {}", x),
-            TooFewTargets(x) => write!(f, "Too few targets - required {}", x),
-            PackNotAvailable(x, y) => write!(f, "Pack {}x{} not available", x, y),
-            TargetNotAvailable(x, y) => write!(f, "Target {}x{} not available", x, y),
-            LockedPackApartWalls(x, y) =>
-                write!(f, "Locked pack {}x{} apart walls", x, y),
-            Locked2x2Block(x, y) => write!(f, "Locked 2x2 block {}x{}", x, y),
+        match *CHECK_ERROR_FORMATTER.read().unwrap() {
+            Some(formatter) => write!(f, "{}", self.format_with(formatter)),
+            None => write!(f, "{}", self.default_message()),
         }
     }
 }
@@ -179,6 +479,15 @@ impl fmt::Display for CheckError {
 impl Error for CheckError {
 }
 
+impl fmt::Display for CheckWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckWarning::PrefilledBox(x, y) =>
+                write!(f, "Pack {}x{} on target at start", x, y),
+        }
+    }
+}
+
 #[derive(PartialEq,Eq,Clone)]
 /// Type contains all check errors.
 pub struct CheckErrors(Vec<CheckError>);
@@ -206,20 +515,61 @@ impl CheckErrors {
     pub(crate) fn push(&mut self, e: CheckError) {
         self.0.push(e)
     }
-    pub(crate) fn len(&self) -> usize {
+    /// Return number of errors.
+    pub fn len(&self) -> usize {
         self.0.len()
     }
+    /// Return true if no errors.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Return iterator over errors.
+    pub fn iter(&self) -> impl Iterator<Item=&CheckError> {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for CheckErrors {
+    type Item = CheckError;
+    type IntoIter = std::vec::IntoIter<CheckError>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a CheckErrors {
+    type Item = &'a CheckError;
+    type IntoIter = std::slice::Iter<'a, CheckError>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
 }
 
 impl Error for CheckErrors {
 }
 
+impl ParseError {
+    /// Return default English message of error.
+    pub fn default_message(&self) -> String {
+        match self {
+            EmptyLines => "Empty lines".to_string(),
+            WrongField(x, y) => format!("Wrong field {}x{}", x, y),
+            WrongSize(x, y) => format!("Wrong size {}x{}", x, y),
+        }
+    }
+    
+    /// Return message of error given by formatter or default message if formatter
+    /// returns None.
+    pub fn format_with(&self, formatter: ParseErrorFormatter) -> String {
+        formatter(self).unwrap_or_else(|| self.default_message())
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            EmptyLines => write!(f, "Empty lines"),
-            WrongField(x, y) => write!(f, "Wrong field {}x{}", x, y),
-            WrongSize(x, y) => write!(f, "Wrong size {}x{}", x, y),
+        match *PARSE_ERROR_FORMATTER.read().unwrap() {
+            Some(formatter) => write!(f, "{}", self.format_with(formatter)),
+            None => write!(f, "{}", self.default_message()),
         }
     }
 }
@@ -229,6 +579,7 @@ impl Error for ParseError {
 
 /// Level parse errors - contains errors and level name
 #[derive(PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize,serde::Deserialize))]
 pub struct LevelParseError {
     pub(crate) number: usize,
     pub(crate) name: String,
@@ -261,6 +612,147 @@ impl fmt::Display for XmlParseError {
 impl Error for XmlParseError {
 }
 
+impl fmt::Display for BinaryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BadMagic => write!(f, "Bad magic of binary levelset"),
+            BadContent => write!(f, "Bad content of binary levelset"),
+        }
+    }
+}
+
+impl Error for BinaryParseError {
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutOfRange(x, y) => write!(f, "Position {}x{} out of range", x, y),
+        }
+    }
+}
+
+impl Error for EditError {
+}
+
+impl fmt::Display for LevelSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelSetError::Io(e) => write!(f, "{}", e),
+            LevelSetError::XmlParse(e) => write!(f, "{}", e),
+            LevelSetError::Xml(e) => write!(f, "{}", e),
+            LevelSetError::ParseInt(e) => write!(f, "{}", e),
+            LevelSetError::BinaryParse(e) => write!(f, "{}", e),
+            LevelSetError::UnsupportedFormat(e) => write!(f, "Unsupported format {}", e),
+            LevelSetError::EmptyLevelSet => write!(f, "Empty levelset"),
+            #[cfg(feature = "serde")]
+            LevelSetError::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LevelSetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LevelSetError::Io(e) => Some(e),
+            LevelSetError::XmlParse(e) => Some(e),
+            LevelSetError::Xml(e) => Some(e),
+            LevelSetError::ParseInt(e) => Some(e),
+            LevelSetError::BinaryParse(e) => Some(e),
+            LevelSetError::UnsupportedFormat(_) => None,
+            LevelSetError::EmptyLevelSet => None,
+            #[cfg(feature = "serde")]
+            LevelSetError::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LevelSetError {
+    fn from(e: io::Error) -> Self {
+        LevelSetError::Io(e)
+    }
+}
+
+impl From<XmlParseError> for LevelSetError {
+    fn from(e: XmlParseError) -> Self {
+        LevelSetError::XmlParse(e)
+    }
+}
+
+impl From<quick_xml::Error> for LevelSetError {
+    fn from(e: quick_xml::Error) -> Self {
+        LevelSetError::Xml(e)
+    }
+}
+
+impl From<ParseIntError> for LevelSetError {
+    fn from(e: ParseIntError) -> Self {
+        LevelSetError::ParseInt(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for LevelSetError {
+    fn from(e: serde_json::Error) -> Self {
+        LevelSetError::Json(e)
+    }
+}
+
+impl From<BinaryParseError> for LevelSetError {
+    fn from(e: BinaryParseError) -> Self {
+        LevelSetError::BinaryParse(e)
+    }
+}
+
+impl Default for FieldCharset {
+    fn default() -> Self {
+        FieldCharset{ empty: " ".to_string(), wall: "#".to_string(),
+                player: "@".to_string(), player_on_target: "+".to_string(),
+                target: ".".to_string(), pack: "$".to_string(),
+                pack_on_target: "*".to_string() }
+    }
+}
+
+impl FieldCharset {
+    /// Return charset with standard characters and alternate symbols:
+    /// '-' and '_' - empty, 'p' - player, 'P' - player on target, 'b' - pack,
+    /// 'B' - pack on target.
+    pub fn extended() -> Self {
+        FieldCharset{ empty: " -_".to_string(), wall: "#".to_string(),
+                player: "@p".to_string(), player_on_target: "+P".to_string(),
+                target: ".".to_string(), pack: "$b".to_string(),
+                pack_on_target: "*B".to_string() }
+    }
+    
+    /// Return field for character or None if character is not field.
+    pub fn field(&self, c: char) -> Option<Field> {
+        [(&self.empty, Empty), (&self.wall, Wall), (&self.player, Player),
+            (&self.player_on_target, PlayerOnTarget), (&self.target, Target),
+            (&self.pack, Pack), (&self.pack_on_target, PackOnTarget)].iter()
+                .find(|(chars, _)| chars.contains(c)).map(|(_, f)| *f)
+    }
+    
+    // translate row to standard characters if it contains only fields and digits
+    // (run-length encoded row). Other rows are returned unchanged.
+    pub(crate) fn translate_row(&self, row: String) -> String {
+        if row.chars().all(|c| c.is_ascii_digit() || self.field(c).is_some()) {
+            row.chars().map(|c| self.field(c).map(Field::to_char).unwrap_or(c)).collect()
+        } else { row }
+    }
+}
+
+// return iterator over fields with positions (x, y, field) in order of area.
+pub(crate) fn area_cells(area: &[Field], width: usize)
+                -> impl Iterator<Item=(usize, usize, Field)> + '_ {
+    area.iter().enumerate().map(move |(i, f)| (i % width, i / width, *f))
+}
+
+// return positions (x, y) of fields that satisfy predicate in order of area.
+pub(crate) fn field_positions(area: &[Field], width: usize, pred: impl Fn(Field) -> bool)
+                -> Vec<(usize, usize)> {
+    area_cells(area, width).filter(|(_, _, f)| pred(*f)).map(|(x, y, _)| (x, y)).collect()
+}
+
 pub(crate) fn char_to_field(x: char) -> Field {
     match x {
         ' ' => Empty,
@@ -274,17 +766,252 @@ pub(crate) fn char_to_field(x: char) -> Field {
     }
 }
 
+// field is serialized as its character in text format.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Field {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.to_char())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Field {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Field, D::Error> {
+        let c = char::deserialize(deserializer)?;
+        if is_not_field(c) {
+            Err(serde::de::Error::invalid_value(serde::de::Unexpected::Char(c),
+                    &"field character"))
+        } else { Ok(char_to_field(c)) }
+    }
+}
+
 pub(crate) fn is_not_field(x: char) -> bool {
     x!=' ' && x!='#' && x!='@' && x!='+' && x!='.' && x!='$' && x!='*'
 }
 
+// return true if row is run-length encoded level row - contains only digits and
+// fields and at least one digit and one field other than space.
+pub(crate) fn is_rle_row(row: &str) -> bool {
+    row.chars().all(|c| c.is_ascii_digit() || !is_not_field(c)) &&
+        row.chars().any(|c| c.is_ascii_digit()) &&
+        row.chars().any(|c| c != ' ' && !is_not_field(c))
+}
+
+/// Maximal width of run-length encoded row after expansion.
+const MAX_RLE_ROW_WIDTH: usize = 4096;
+
+// expand run-length encoded row (count followed by field, for example '3#2 $').
+// Return expanded row or column of wrong field in encoded row. Field is also wrong
+// if expanded row would be wider than MAX_RLE_ROW_WIDTH.
+pub(crate) fn expand_rle(row: &str) -> Result<String, usize> {
+    let mut out = String::new();
+    let mut count: Option<usize> = None;
+    for (i, c) in row.chars().enumerate() {
+        if let Some(d) = c.to_digit(10) {
+            count = Some(count.unwrap_or(0).checked_mul(10)
+                    .and_then(|x| x.checked_add(d as usize))
+                    .filter(|x| *x <= MAX_RLE_ROW_WIDTH).ok_or(i)?);
+        } else if is_not_field(c) {
+            return Err(i);
+        } else {
+            let n = count.take().unwrap_or(1);
+            if out.len() + n > MAX_RLE_ROW_WIDTH {
+                return Err(i);
+            }
+            (0..n).for_each(|_| out.push(c));
+        }
+    }
+    if count.is_some() {
+        // count without field
+        Err(row.chars().count())
+    } else { Ok(out) }
+}
+
 /// Possible game result.
-#[derive(PartialEq,Eq,Copy,Clone)]
+#[derive(PartialEq,Eq,Debug,Copy,Clone)]
 pub enum GameResult {
     /// If level solved.
     Solved,
     /// If game canceled.
     Canceled,
-    // if game quit.
+    /// If game quit.
     Quit,
 }
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::Solved => write!(f, "Level has been solved."),
+            GameResult::Canceled => write!(f, "Level has been canceled."),
+            GameResult::Quit => write!(f, "Quit."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_field_deserialize() {
+        use serde::Deserialize;
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{CharDeserializer,Error as ValueError};
+        let de: CharDeserializer<ValueError> = '*'.into_deserializer();
+        assert_eq!(Ok(PackOnTarget), Field::deserialize(de));
+        let de: CharDeserializer<ValueError> = ' '.into_deserializer();
+        assert_eq!(Ok(Empty), Field::deserialize(de));
+        let de: CharDeserializer<ValueError> = 'x'.into_deserializer();
+        assert!(Field::deserialize(de).is_err());
+    }
+    
+    #[test]
+    fn test_field_to_char() {
+        for f in [Empty, Wall, Player, PlayerOnTarget, Target, Pack, PackOnTarget] {
+            assert!(!is_not_field(f.to_char()));
+            assert_eq!(f, char_to_field(f.to_char()));
+        }
+        assert_eq!('*', PackOnTarget.to_char());
+        assert_eq!('+', PlayerOnTarget.to_char());
+    }
+    
+    #[test]
+    fn test_game_result_display() {
+        assert_eq!("Level has been solved.", GameResult::Solved.to_string());
+        assert_eq!("Level has been canceled.", GameResult::Canceled.to_string());
+        assert_eq!("Quit.", GameResult::Quit.to_string());
+    }
+    
+    #[test]
+    fn test_expand_rle() {
+        assert_eq!(Ok("###     $..".to_string()), expand_rle("3#5 $2."));
+        assert_eq!(Ok("#@$.#".to_string()), expand_rle("#@$.#"));
+        assert_eq!(Ok("#".repeat(12)), expand_rle("12#"));
+        assert_eq!(Err(3), expand_rle("2#3"));
+        assert_eq!(Err(2), expand_rle("2#x"));
+        // too long runs
+        assert_eq!(Err(3), expand_rle("99999999999999999999#"));
+        assert_eq!(Err(4), expand_rle("4000000000#"));
+        assert_eq!(Err(3), expand_rle("4097#"));
+        assert_eq!(Ok("#".repeat(4096)), expand_rle("4096#"));
+        assert_eq!(Err(9), expand_rle("4000#96#1 "));
+        assert!(is_rle_row("3#5 $2."));
+        assert!(!is_rle_row("#@$.#"));
+        assert!(!is_rle_row("12"));
+        assert!(!is_rle_row("Level 12"));
+    }
+    
+    #[test]
+    fn test_field_charset() {
+        let cs = FieldCharset::default();
+        for f in [Empty, Wall, Player, PlayerOnTarget, Target, Pack, PackOnTarget] {
+            assert_eq!(Some(f), cs.field(f.to_char()));
+        }
+        assert_eq!(None, cs.field('-'));
+        assert_eq!("-#p$.#", cs.translate_row("-#p$.#".to_string()));
+        let cs = FieldCharset::extended();
+        assert_eq!(Some(Empty), cs.field('_'));
+        assert_eq!(Some(PackOnTarget), cs.field('B'));
+        assert_eq!(None, cs.field('x'));
+        assert_eq!(" #@$.#", cs.translate_row("-#p$.#".to_string()));
+        assert_eq!("3#2 *", cs.translate_row("3#2_B".to_string()));
+        assert_eq!("By author", cs.translate_row("By author".to_string()));
+    }
+    
+    #[test]
+    fn test_direction_moves() {
+        assert_eq!([Left, Right, Up, Down], Direction::MOVES);
+        assert_eq!(vec![Left, Right, Up, Down], Direction::all_moves().collect::<Vec<_>>());
+    }
+    
+    #[test]
+    fn test_check_errors_iter() {
+        let mut errors = CheckErrors::new();
+        assert!(errors.is_empty());
+        errors.push(PackNotAvailable(2, 3));
+        errors.push(LockedPackApartWalls(4, 1));
+        assert!(!errors.is_empty());
+        assert_eq!(2, errors.len());
+        assert_eq!(vec![&PackNotAvailable(2, 3), &LockedPackApartWalls(4, 1)],
+                errors.iter().collect::<Vec<_>>());
+        let mut places = vec![];
+        for e in &errors {
+            if let PackNotAvailable(x, y) = e { places.push((*x, *y)); }
+        }
+        assert_eq!(vec![(2, 3)], places);
+        assert_eq!(vec![PackNotAvailable(2, 3), LockedPackApartWalls(4, 1)],
+                errors.into_iter().collect::<Vec<_>>());
+    }
+    
+    #[test]
+    fn test_direction_helpers() {
+        let all = [Left, Right, Up, Down, PushLeft, PushRight, PushUp, PushDown, NoDirection,
+                PullLeft, PullRight, PullUp, PullDown];
+        for d in all {
+            assert_eq!(d, d.opposite().opposite());
+            assert_eq!(d.is_push(), d.opposite().is_push());
+            assert_eq!(d.is_pull(), d.opposite().is_pull());
+            assert!(!d.as_move().is_push());
+            assert!(!d.as_move().is_pull());
+            assert_eq!(d.as_move().opposite(), d.opposite().as_move());
+        }
+        assert_eq!(Right, Left.opposite());
+        assert_eq!(PushDown, PushUp.opposite());
+        assert_eq!(NoDirection, NoDirection.opposite());
+        assert_eq!(vec![false, false, false, false, true, true, true, true, false,
+                false, false, false, false],
+                all.iter().map(|d| d.is_push()).collect::<Vec<_>>());
+        assert_eq!(PullRight, PullLeft.opposite());
+        assert_eq!(Up, PullUp.as_move());
+        assert_eq!(None, PullUp.to_lurd());
+        assert_eq!(Left, PushLeft.as_move());
+        assert_eq!(Down, Down.as_move());
+        assert_eq!(NoDirection, NoDirection.as_move());
+    }
+    
+    // global formatters are not changed by tests, because other tests format
+    // errors in parallel.
+    #[test]
+    fn test_error_formatter() {
+        let check_formatter: CheckErrorFormatter = |e| match e {
+            NoPlayer => Some("Brak gracza".to_string()),
+            _ => None,
+        };
+        let parse_formatter: ParseErrorFormatter = |e| match e {
+            EmptyLines => Some("Puste linie".to_string()),
+            _ => None,
+        };
+        assert_eq!("Brak gracza", NoPlayer.format_with(check_formatter));
+        assert_eq!("Level open", LevelOpen.format_with(check_formatter));
+        assert_eq!("Puste linie", EmptyLines.format_with(parse_formatter));
+        assert_eq!("Wrong size 3x4", WrongSize(3, 4).format_with(parse_formatter));
+        assert_eq!("No player", NoPlayer.default_message());
+        assert_eq!("Empty lines", EmptyLines.default_message());
+    }
+    
+    #[test]
+    fn test_direction_from_name() {
+        assert_eq!(Some(Left), Direction::from_name("left"));
+        assert_eq!(Some(Up), Direction::from_name("UP"));
+        assert_eq!(Some(Down), Direction::from_name("Down"));
+        assert_eq!(Some(PushLeft), Direction::from_name("pushleft"));
+        assert_eq!(Some(PushRight), Direction::from_name("PushRight"));
+        assert_eq!(Some(PullDown), Direction::from_name("PullDown"));
+        assert_eq!(Some(NoDirection), Direction::from_name("nodirection"));
+        assert_eq!(None, Direction::from_name("push left"));
+        assert_eq!(None, Direction::from_name("forward"));
+        assert_eq!(None, Direction::from_name(""));
+    }
+    
+    #[test]
+    fn test_direction_from_lurd() {
+        assert_eq!(vec![Some(Left), Some(Up), Some(Right), Some(Down), Some(PushLeft),
+                Some(PushUp), Some(PushRight), Some(PushDown), None],
+                "lurdLURDx".chars().map(Direction::from_lurd).collect::<Vec<_>>());
+        for c in "lurdLURD".chars() {
+            assert_eq!(Some(c), Direction::from_lurd(c).unwrap().to_lurd());
+        }
+        assert_eq!(None, NoDirection.to_lurd());
+    }
+}
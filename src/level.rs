@@ -17,22 +17,78 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self,BufRead};
+
+use int_enum::IntEnum;
+
 use crate::defs::*;
 
 use Field::*;
-use Direction::*;
 use CheckError::*;
 use ParseError::*;
 
-/// Level in game. Name is optional name - can be empty. Width and height determines
-/// dimensions of the level. An area is fields of level ordered from top to bottom and
-/// from left to right.
+/// Level in game. Name is optional name - can be empty. Identifier is identifier of
+/// the level in source (for example 'Id' in XML) - by default it is same as name.
+/// Width and height determines dimensions of the level. An area is fields of level
+/// ordered from top to bottom and from left to right.
 #[derive(PartialEq,Eq,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize,serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "LevelData"))]
 pub struct Level {
     pub(crate) name: String,
+    pub(crate) id: String,
     pub(crate) width: usize,
     pub(crate) height: usize,
     pub(crate) area: Vec<Field>,
+    pub(crate) annotations: Vec<(usize, usize, String)>,
+    pub(crate) comment: String,
+}
+
+// deserialized level before validation of its dimensions.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LevelData {
+    name: String,
+    id: String,
+    width: usize,
+    height: usize,
+    area: Vec<Field>,
+    annotations: Vec<(usize, usize, String)>,
+    comment: String,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<LevelData> for Level {
+    type Error = ParseError;
+    
+    fn try_from(l: LevelData) -> Result<Level, ParseError> {
+        if l.width.checked_mul(l.height) != Some(l.area.len()) {
+            return Err(WrongSize(l.width, l.height));
+        }
+        Ok(Level{ name: l.name, id: l.id, width: l.width, height: l.height, area: l.area,
+                annotations: l.annotations, comment: l.comment })
+    }
+}
+
+/// Structural statistics of the level.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct LevelStats {
+    /// Width of the level.
+    pub width: usize,
+    /// Height of the level.
+    pub height: usize,
+    /// Number of walls.
+    pub walls: usize,
+    /// Number of packs (including packs on targets).
+    pub packs: usize,
+    /// Number of targets (including targets with pack or player).
+    pub targets: usize,
+    /// Number of empty fields (without any object, including fields outside level).
+    pub empties: usize,
+    /// Number of fields (non-walls) reachable by player if packs would be ignored.
+    pub reachable_floor: usize,
 }
 
 impl Level {
@@ -40,6 +96,10 @@ impl Level {
     pub fn name(&self) -> &String {
         &self.name
     }
+    /// Get identifier of the level.
+    pub fn id(&self) -> &String {
+        &self.id
+    }
     /// Get width of the level.
     pub fn width(&self) -> usize {
         self.width
@@ -52,17 +112,97 @@ impl Level {
     pub fn area(&self) -> &Vec<Field> {
         &self.area
     }
+    /// Get iterator over rows of the area.
+    pub fn rows(&self) -> impl Iterator<Item=&[Field]> {
+        self.area.chunks(self.width.max(1))
+    }
+    /// Get row of the area or None if row is out of range.
+    pub fn row(&self, y: usize) -> Option<&[Field]> {
+        if y < self.height {
+            Some(&self.area[y*self.width..(y+1)*self.width])
+        } else { None }
+    }
+    /// Return rows of the level in text format ('#' - wall, '$' - pack,
+    /// '.' - target, '*' - pack on target, '@' - player, '+' - player on target).
+    /// Trailing spaces are trimmed.
+    pub fn to_string_lines(&self) -> Vec<String> {
+        self.rows().map(|row| {
+            let line: String = row.iter().map(|f| f.to_char()).collect();
+            line.trim_end().to_string()
+        }).collect()
+    }
+    /// Return iterator over fields with their positions (x, y, field) in order
+    /// of area (from top to bottom and from left to right).
+    pub fn cells(&self) -> impl Iterator<Item=(usize, usize, Field)> + '_ {
+        area_cells(&self.area, self.width)
+    }
+    
+    /// Return positions (x, y) of packs (including packs on targets).
+    pub fn boxes(&self) -> Vec<(usize, usize)> {
+        field_positions(&self.area, self.width, Field::is_pack)
+    }
+    
+    /// Return positions (x, y) of targets (including occupied targets).
+    pub fn targets(&self) -> Vec<(usize, usize)> {
+        field_positions(&self.area, self.width, Field::is_target)
+    }
+    
+    /// Return position (x, y) of player or None if level doesn't have player.
+    pub fn player(&self) -> Option<(usize, usize)> {
+        self.area.iter().position(|f| f.is_player()).map(|i| (i % self.width, i / self.width))
+    }
+    
+    /// Get annotations of the level - text hints attached to fields (x, y, text).
+    pub fn annotations(&self) -> &Vec<(usize, usize, String)> {
+        &self.annotations
+    }
+    
+    /// Get comment of the level - additional comment lines (author, difficulty, etc.)
+    /// separated by newline. Empty if level doesn't have comment.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+    
+    /// Get expected number of moves and pushes from comment line in form
+    /// 'moves=97 pushes=42'. Return None if level doesn't have such line.
+    pub fn par(&self) -> Option<(usize, usize)> {
+        self.comment.lines().find_map(|l| {
+            let (mut moves, mut pushes) = (None, None);
+            for (key, value) in l.split_whitespace().filter_map(|w| w.split_once('=')) {
+                match key {
+                    "moves" => moves = value.parse().ok(),
+                    "pushes" => pushes = value.parse().ok(),
+                    _ => {}
+                }
+            }
+            Some((moves?, pushes?))
+        })
+    }
+    
+    /// Return level with given comment.
+    pub fn with_comment(mut self, comment: &str) -> Level {
+        self.comment = comment.to_string();
+        self
+    }
+    
+    /// Return level with added annotation attached to field at given position.
+    pub fn with_annotation(mut self, x: usize, y: usize, text: &str) -> Level {
+        self.annotations.push((x, y, text.to_string()));
+        self
+    }
     
     /// Create empty level
     pub fn empty() -> Level {
-        Level{ name: String::new(), width: 0, height: 0, area: vec![] }
+        Level{ name: String::new(), id: String::new(), width: 0, height: 0, area: vec![],
+                annotations: vec![], comment: String::new() }
     }
     
     // Create level from area data.
     pub fn new(name: &str, width: usize, height: usize, area: Vec<Field>)
                     -> Result<Level, ParseError> {
         if area.len() == width*height {
-            Ok(Level{ name: String::from(name), width, height, area })
+            Ok(Level{ name: String::from(name), id: String::from(name), width, height,
+                    area, annotations: vec![], comment: String::new() })
         } else {
             Err(WrongSize(width, height))
         }
@@ -80,79 +220,86 @@ impl Level {
             return Err(WrongField(pp%width, pp/width));
         }
         let area: Vec<Field> = chrs2.map(char_to_field).collect();
-        Ok(Level{ name: String::from(name), width, height, area: area })
+        Ok(Level{ name: String::from(name), id: String::from(name), width, height,
+                area: area, annotations: vec![], comment: String::new() })
     }
     
-    fn check_level_by_fill(&self, px: usize, py: usize, errors: &mut CheckErrors) {
-        #[derive(Debug)]
-        struct StackItem{ x: usize, y: usize, d: Direction }
-        // find player
+    /// Parse level from lines. Width of level is maximal length of line and height
+    /// is number of lines. Short lines are filled by empty fields. Return I/O error
+    /// if reading of lines failed, otherwise return level or parse error.
+    pub fn from_lines<B: BufRead>(name: &str, lines: io::Lines<B>)
+                    -> io::Result<Result<Level, ParseError>> {
+        let lines = lines.collect::<io::Result<Vec<String>>>()?;
+        Ok(Self::from_rows(name, &lines))
+    }
+    
+    /// Parse level from run-length encoded rows (count followed by field,
+    /// for example '3#5 $2.'). Rows are separated by '|' or new line.
+    pub fn from_rle(name: &str, rle: &str) -> Result<Level, ParseError> {
+        let mut rows = vec![];
+        for (y, row) in rle.split(['|', '\n']).enumerate() {
+            rows.push(expand_rle(row).map_err(|x| WrongField(x, y))?);
+        }
+        // skip trailing empty row
+        if rows.last().is_some_and(|r| r.is_empty()) { rows.pop(); }
+        Self::from_rows(name, &rows)
+    }
+    
+    // create level from rows. Short rows are filled by empty fields.
+    fn from_rows(name: &str, lines: &[String]) -> Result<Level, ParseError> {
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let height = lines.len();
+        if width == 0 {
+            return Err(EmptyLines);
+        }
+        let mut area = vec![Empty; width*height];
+        for (y, l) in lines.iter().enumerate() {
+            if let Some(pp) = l.chars().position(is_not_field) {
+                return Err(WrongField(pp, y));
+            }
+            l.chars().enumerate().for_each(|(x, c)| area[y*width + x] = char_to_field(c));
+        }
+        Ok(Level{ name: String::from(name), id: String::from(name), width, height,
+                area, annotations: vec![], comment: String::new() })
+    }
+    
+    // fill area from position. Return filled fields and true if fill touches frames
+    // (contains non-wall field at boundary of the area).
+    fn fill_from(&self, px: usize, py: usize) -> (Vec<bool>, bool) {
         let mut filled = vec![false; self.width*self.height];
-        let mut stk = vec![StackItem{x: px, y: py, d:Left}];
-        
-        let mut touch_frames = false;
-        
-        while stk.len() != 0 {
-            if let Some(it) = stk.last_mut() {
-                if self.area[it.y*self.width + it.x] == Wall ||
-                    (filled[it.y*self.width + it.x] && it.d==Left) {
-                    stk.pop();  // if wall or already filled
-                } else {
-                    // fill this field
-                    filled[it.y*self.width + it.x] = true;
-                    // get next position
-                    let next_pos = match it.d {
-                        Left => {
-                            it.d = Right;
-                            if it.x > 0 {
-                                Some((it.x-1, it.y))
-                            } else {
-                                touch_frames = true;
-                                None
-                            }
-                        },
-                        Right => {
-                            it.d = Down;
-                            if it.x+1 < self.width {
-                                Some((it.x+1, it.y))
-                            } else {
-                                touch_frames = true;
-                                None
-                            }
-                        }
-                        Down => {
-                            it.d = Up;
-                            if it.y > 0 {
-                                Some((it.x, it.y-1))
-                            } else {
-                                touch_frames = true;
-                                None
-                            }
-                        }
-                        Up => {
-                            it.d = NoDirection;
-                            if it.y+1 < self.height {
-                                Some((it.x, it.y+1))
-                            } else {
-                                touch_frames = true;
-                                None
-                            }
-                        }
-                        _ => { None }
-                    };
-                    if let Some((x,y)) = next_pos {
-                        stk.push(StackItem{x,y,d:Left}); // push next step
-                    } else if it.d == NoDirection {
-                        stk.pop();  // all is filled
-                    }
+        let start = py*self.width + px;
+        if self.area[start] == Wall {
+            return (filled, false);
+        }
+        let mut stk = vec![start];
+        filled[start] = true;
+        while let Some(pos) = stk.pop() {
+            let (x, y) = (pos % self.width, pos / self.width);
+            let neighbors = [
+                if x > 0 { Some(pos-1) } else { None },
+                if x+1 < self.width { Some(pos+1) } else { None },
+                if y > 0 { Some(pos-self.width) } else { None },
+                if y+1 < self.height { Some(pos+self.width) } else { None }];
+            for npos in neighbors.iter().flatten() {
+                if !filled[*npos] && self.area[*npos] != Wall {
+                    filled[*npos] = true;
+                    stk.push(*npos);
                 }
             }
         }
-        
+        // filled fields are never walls
+        let touch_frames = self.cells().any(|(x, y, _)| filled[y*self.width + x] &&
+                (x == 0 || y == 0 || x+1 == self.width || y+1 == self.height));
+        (filled, touch_frames)
+    }
+    
+    fn check_level_by_fill(&self, px: usize, py: usize, errors: &mut CheckErrors) {
+        let (filled, touch_frames) = self.fill_from(px, py);
         if touch_frames {
             errors.push(LevelOpen);
         }
-        // check availability
+        // check availability. Packs on targets are skipped - they are already
+        // on place, therefore they don't need to be available.
         self.area.iter().enumerate().for_each(|(i,x)| {
             if *x == Pack && !filled[i] {
                 errors.push(PackNotAvailable(i % self.width, i / self.width))
@@ -165,9 +312,250 @@ impl Level {
         });
     }
     
+    /// Parse level from string and check it. Panics if level can not be parsed
+    /// or if it is invalid. Designed for tests and examples.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn checked(name: &str, width: usize, height: usize, astr: &str) -> Level {
+        let level = match Level::from_str(name, width, height, astr) {
+            Ok(level) => level,
+            Err(e) => panic!("Level {} can not be parsed: {}", name, e),
+        };
+        if let Err(errors) = level.check() {
+            panic!("Level {} is invalid: {}", name, errors);
+        }
+        level
+    }
+    
+    /// Return number of walls.
+    pub fn wall_count(&self) -> usize {
+        self.area.iter().filter(|x| **x == Wall).count()
+    }
+    
+    /// Return structural statistics of the level.
+    pub fn stats(&self) -> LevelStats {
+        let mut stats = LevelStats{ width: self.width, height: self.height, walls: 0,
+                packs: 0, targets: 0, empties: 0, reachable_floor: 0 };
+        let mut player = None;
+        for (i, x) in self.area.iter().enumerate() {
+            match x {
+                Wall => stats.walls += 1,
+                Empty => stats.empties += 1,
+                _ => {}
+            }
+            if x.is_pack() { stats.packs += 1; }
+            if x.is_target() { stats.targets += 1; }
+            if x.is_player() && player.is_none() { player = Some(i); }
+        }
+        if let Some(pp) = player {
+            let (filled, _) = self.fill_from(pp % self.width, pp / self.width);
+            stats.reachable_floor = filled.iter().filter(|x| **x).count();
+        }
+        stats
+    }
+    
+    /// Return mirrored level (flipped horizontally).
+    pub fn mirror(&self) -> Level {
+        let mut area = Vec::with_capacity(self.area.len());
+        for row in self.rows() {
+            area.extend(row.iter().rev());
+        }
+        let annotations = self.annotations.iter()
+                .map(|(x, y, t)| (self.width-1-x, *y, t.clone())).collect();
+        Level{ name: self.name.clone(), id: self.id.clone(), width: self.width,
+                height: self.height, area, annotations,
+                comment: self.comment.clone() }
+    }
+    
+    /// Return level rotated clockwise by 90 degrees.
+    pub fn rotate(&self) -> Level {
+        let mut area = Vec::with_capacity(self.area.len());
+        for y in 0..self.width {
+            for x in 0..self.height {
+                area.push(self.area[(self.height-1-x)*self.width + y]);
+            }
+        }
+        let annotations = self.annotations.iter()
+                .map(|(x, y, t)| (self.height-1-y, *x, t.clone())).collect();
+        Level{ name: self.name.clone(), id: self.id.clone(), width: self.height,
+                height: self.width, area, annotations,
+                comment: self.comment.clone() }
+    }
+    
+    // return bounding box (x0, y0, x1, y1) of non-empty fields or None if level
+    // contains only empty fields.
+    fn bounding_box(&self) -> Option<(usize, usize, usize, usize)> {
+        let mut bbox: Option<(usize, usize, usize, usize)> = None;
+        for (i, x) in self.area.iter().enumerate() {
+            if *x != Empty {
+                let (ix, iy) = (i % self.width, i / self.width);
+                bbox = Some(match bbox {
+                    Some((x0, y0, x1, y1)) => (x0.min(ix), y0.min(iy), x1.max(ix), y1.max(iy)),
+                    None => (ix, iy, ix, iy),
+                });
+            }
+        }
+        bbox
+    }
+    
+    /// Crop empty rows and columns around level. Annotations are moved with fields
+    /// and annotations outside cropped level are removed.
+    pub fn normalize(&mut self) {
+        if let Some((x0, y0, x1, y1)) = self.bounding_box() {
+            let width = x1 - x0 + 1;
+            let height = y1 - y0 + 1;
+            let mut area = Vec::with_capacity(width*height);
+            for y in y0..=y1 {
+                area.extend_from_slice(&self.area[y*self.width + x0..=y*self.width + x1]);
+            }
+            self.annotations.retain(|(x, y, _)| (x0..=x1).contains(x) && (y0..=y1).contains(y));
+            self.annotations.iter_mut().for_each(|(x, y, _)| { *x -= x0; *y -= y0; });
+            self.width = width;
+            self.height = height;
+            self.area = area;
+        }
+    }
+    
+    /// Return level with cropped empty rows and columns around level.
+    pub fn normalized(mut self) -> Level {
+        self.normalize();
+        self
+    }
+    
+    /// Return canonical key of level - lexicographically smallest serialization
+    /// (dimensions and fields of cropped level) among all 8 rotations and
+    /// reflections of level. Rotated or mirrored levels have same canonical key.
+    pub fn canonical_key(&self) -> Vec<u8> {
+        let key_of = |l: &Level| {
+            let mut key = Vec::with_capacity(8 + l.area.len());
+            key.extend_from_slice(&(l.width as u32).to_be_bytes());
+            key.extend_from_slice(&(l.height as u32).to_be_bytes());
+            key.extend(l.area.iter().map(|f| f.int_value()));
+            key
+        };
+        let mut level = Level{ name: String::new(), id: String::new(), width: self.width,
+                height: self.height, area: self.area.clone(), annotations: vec![],
+                comment: String::new() }.normalized();
+        let mut keys = Vec::with_capacity(8);
+        for _ in 0..4 {
+            keys.push(key_of(&level));
+            keys.push(key_of(&level.mirror()));
+            level = level.rotate();
+        }
+        keys.into_iter().min().unwrap()
+    }
+    
+    // rebuild level with new dimensions. map returns new position of field
+    // or None if field should be removed. Annotations are moved with fields.
+    fn remap<F>(&mut self, width: usize, height: usize, map: F)
+            where F: Fn(usize, usize) -> Option<(usize, usize)> {
+        let mut area = vec![Empty; width*height];
+        for (i, f) in self.area.iter().enumerate() {
+            if let Some((nx, ny)) = map(i % self.width, i / self.width) {
+                area[ny*width + nx] = *f;
+            }
+        }
+        let annotations = self.annotations.drain(..).filter_map(|(x, y, text)|
+                map(x, y).map(|(nx, ny)| (nx, ny, text))).collect();
+        self.annotations = annotations;
+        self.width = width;
+        self.height = height;
+        self.area = area;
+    }
+    
+    /// Set field at given position.
+    pub fn set_field(&mut self, x: usize, y: usize, f: Field) -> Result<(), EditError> {
+        if x >= self.width || y >= self.height {
+            return Err(EditError::OutOfRange(x, y));
+        }
+        self.area[y*self.width + x] = f;
+        Ok(())
+    }
+    
+    /// Resize level. Top-left part of level is preserved and new fields are empty.
+    /// Annotations outside new level are removed.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.remap(width, height, |x, y| if x < width && y < height {
+            Some((x, y)) } else { None });
+    }
+    
+    /// Insert empty row before row y (y can be equal to height).
+    pub fn insert_row(&mut self, y: usize) -> Result<(), EditError> {
+        if y > self.height {
+            return Err(EditError::OutOfRange(0, y));
+        }
+        self.remap(self.width, self.height + 1, |ix, iy|
+                Some((ix, if iy >= y { iy + 1 } else { iy })));
+        Ok(())
+    }
+    
+    /// Insert empty column before column x (x can be equal to width).
+    pub fn insert_col(&mut self, x: usize) -> Result<(), EditError> {
+        if x > self.width {
+            return Err(EditError::OutOfRange(x, 0));
+        }
+        self.remap(self.width + 1, self.height, |ix, iy|
+                Some((if ix >= x { ix + 1 } else { ix }, iy)));
+        Ok(())
+    }
+    
+    /// Remove row y. Annotations in this row are removed.
+    pub fn remove_row(&mut self, y: usize) -> Result<(), EditError> {
+        if y >= self.height {
+            return Err(EditError::OutOfRange(0, y));
+        }
+        self.remap(self.width, self.height - 1, |ix, iy| match iy.cmp(&y) {
+            Ordering::Less => Some((ix, iy)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some((ix, iy - 1)),
+        });
+        Ok(())
+    }
+    
+    /// Remove column x. Annotations in this column are removed.
+    pub fn remove_col(&mut self, x: usize) -> Result<(), EditError> {
+        if x >= self.width {
+            return Err(EditError::OutOfRange(x, 0));
+        }
+        self.remap(self.width - 1, self.height, |ix, iy| match ix.cmp(&x) {
+            Ordering::Less => Some((ix, iy)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some((ix - 1, iy)),
+        });
+        Ok(())
+    }
+    
+    /// Return true if outermost ring of bounding box of the level (without empty
+    /// fields around level) contains only walls.
+    pub fn is_rectangle_complete(&self) -> bool {
+        if let Some((x0, y0, x1, y1)) = self.bounding_box() {
+            (x0..=x1).all(|x| self.area[y0*self.width + x] == Wall &&
+                        self.area[y1*self.width + x] == Wall) &&
+            (y0..=y1).all(|y| self.area[y*self.width + x0] == Wall &&
+                        self.area[y*self.width + x1] == Wall)
+        } else { false }
+    }
+    
+    /// Check level and return also warnings. Warnings are informational and
+    /// they don't make level invalid.
+    pub fn check_detailed(&self) -> (Result<(), CheckErrors>, Vec<CheckWarning>) {
+        (self.check(), self.check_warnings())
+    }
+    
+    /// Return warnings of level.
+    pub fn check_warnings(&self) -> Vec<CheckWarning> {
+        self.area.iter().enumerate().filter(|(_,x)| **x == PackOnTarget)
+            .map(|(i,_)| CheckWarning::PrefilledBox(i % self.width, i / self.width))
+            .collect()
+    }
+    
     /// Check level.
     pub fn check(&self) -> Result<(), CheckErrors> {
         let mut errors = CheckErrors::new();
+        // other checks require area with walls around level
+        if self.width < 3 || self.height < 3 || self.area.len() != self.width*self.height {
+            errors.push(DegenerateSize(self.width, self.height));
+            return Err(errors);
+        }
         let players_num = self.area.iter().filter(|x| x.is_player()).count();
         match players_num {
             0 => errors.push(NoPlayer),
@@ -188,55 +576,50 @@ impl Level {
             let y = pp / self.width;
             self.check_level_by_fill(x, y, &mut errors);
         }
-        // find locks
-        for iy in 0..self.height-1 {
-            for ix in 0..self.width-1 {
-                let field_ul = self.area[iy*self.width + ix];
-                let field_ur = self.area[iy*self.width + ix+1];
-                let field_dl = self.area[(iy+1)*self.width + ix];
-                let field_dr = self.area[(iy+1)*self.width + ix+1];
-                if (field_ul.is_pack() || field_ul==Wall)  &&
-                    (field_ur.is_pack() || field_ur==Wall) &&
-                    (field_dl.is_pack() || field_dl==Wall) &&
-                    (field_dr.is_pack() || field_dr==Wall) {
-                    let mut packs = 0;
-                    if field_ul.is_pack() { packs+=1; }
-                    if field_ur.is_pack() { packs+=1; }
-                    if field_dl.is_pack() { packs+=1; }
-                    if field_dr.is_pack() { packs+=1; }
-                    let mut packs_on_target = 0;
-                    if field_ul == PackOnTarget { packs_on_target+=1; }
-                    if field_ur == PackOnTarget { packs_on_target+=1; }
-                    if field_dl == PackOnTarget { packs_on_target+=1; }
-                    if field_dr == PackOnTarget { packs_on_target+=1; }
-                    // only if not all packs in target
-                    if packs_on_target != packs {
-                        errors.push(Locked2x2Block(ix, iy));
-                    }
-                }
+        find_locks(self.width, self.height, &self.area, &mut errors);
+        
+        if !errors.is_empty() {
+            Err(errors)
+        } else { Ok(()) }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_lines().join("\n"))
+    }
+}
+
+// return true if 2x2 block with top-left field at position contains only packs
+// and walls and some of its packs is not on target.
+pub(crate) fn is_locked_block(width: usize, area: &[Field], pos: usize) -> bool {
+    let block = [area[pos], area[pos+1], area[pos+width], area[pos+width+1]];
+    block.iter().all(|f| f.is_pack() || *f == Wall) && block.contains(&Pack)
+}
+
+// return true if pack not on target at position is in corner of walls.
+// Position must not lie at edge of area.
+pub(crate) fn is_locked_in_corner(width: usize, area: &[Field], pos: usize) -> bool {
+    let vert = area[pos-width] == Wall || area[pos+width] == Wall;
+    let horiz = area[pos-1] == Wall || area[pos+1] == Wall;
+    area[pos] == Pack && vert && horiz
+}
+
+// find locked packs (2x2 blocks and packs apart walls) in area and push errors.
+fn find_locks(width: usize, height: usize, area: &[Field], errors: &mut CheckErrors) {
+    for iy in 0..height.saturating_sub(1) {
+        for ix in 0..width.saturating_sub(1) {
+            if is_locked_block(width, area, iy*width + ix) {
+                errors.push(Locked2x2Block(ix, iy));
             }
         }
-        for iy in 1..self.height-1 {
-            for ix in 1..self.width-1 {
-                let field_u = self.area[(iy-1)*self.width + ix];
-                let field_l = self.area[iy*self.width + ix-1];
-                let field = self.area[iy*self.width + ix];
-                let field_r = self.area[iy*self.width + ix+1];
-                let field_d = self.area[(iy+1)*self.width + ix];
-                if field == Pack {
-                    if (field_u == Wall && (field_l == Wall || field_r == Wall)) ||
-                        (field_d == Wall && (field_l == Wall || field_r == Wall)) ||
-                        (field_l == Wall && (field_u == Wall || field_d == Wall)) ||
-                        (field_r == Wall && (field_u == Wall || field_d == Wall)) {
-                        errors.push(LockedPackApartWalls(ix, iy));
-                    }
-                }
+    }
+    for iy in 1..height.saturating_sub(1) {
+        for ix in 1..width.saturating_sub(1) {
+            if is_locked_in_corner(width, area, iy*width + ix) {
+                errors.push(LockedPackApartWalls(ix, iy));
             }
         }
-        
-        if errors.len() != 0 {
-            Err(errors)
-        } else { Ok(()) }
     }
 }
 
@@ -309,6 +692,261 @@ mod test {
         assert_eq!(Err(WrongSize(8,7)), levelb);
     }
     
+    #[test]
+    fn test_normalize() {
+        let rows = ["         ", "  ###### ", " #      #", " #@  ...#", " #   $$$#",
+                " #      #", "  ###### ", "         "];
+        let level = Level::from_str("git", 9, 8, &rows.concat()).unwrap()
+                    .with_annotation(5, 3, "target")
+                    .with_annotation(0, 7, "outside");
+        let cropped: String = rows[1..7].iter().map(|r| &r[1..]).collect();
+        let expected = Level::from_str("git", 8, 6, &cropped).unwrap()
+                    .with_annotation(4, 2, "target");
+        assert_eq!(Ok(()), level.check());
+        let level = level.normalized();
+        assert_eq!(expected, level);
+        assert_eq!(Ok(()), level.check());
+        
+        // already normalized level and empty level
+        let mut level2 = Level::from_str("git", 8, 6, &cropped).unwrap()
+                    .with_annotation(4, 2, "target");
+        level2.normalize();
+        assert_eq!(expected, level2);
+        let mut empty = Level::from_str("empty", 2, 2, "    ").unwrap();
+        empty.normalize();
+        assert_eq!(Level::from_str("empty", 2, 2, "    ").unwrap(), empty);
+    }
+    
+    #[test]
+    fn test_canonical_key() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        // rotated by 90 degrees with empty column and row around
+        let rows = ["       ", " ####  ", "#  @ # ", "#    # ", "#    # ", "# $. # ",
+                "# $. # ", "# $. # ", " ####  "];
+        let rotated = Level::from_str("rotated", 7, 9, &rows.concat()).unwrap();
+        let key = level.canonical_key();
+        assert_eq!(key, rotated.canonical_key());
+        assert_eq!(key, level.rotate().canonical_key());
+        assert_eq!(key, level.mirror().rotate().rotate().canonical_key());
+        let other = Level::from_str("other", 8, 6,
+            " ###### \
+             #      #\
+             # @ ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert_ne!(key, other.canonical_key());
+    }
+    
+    #[test]
+    fn test_level_edit() {
+        let mut level = Level::from_str("edit", 3, 2, "#@#$.#").unwrap()
+                    .with_annotation(1, 1, "target").with_annotation(2, 0, "wall");
+        assert_eq!(Ok(()), level.set_field(0, 1, Empty));
+        assert_eq!(Err(EditError::OutOfRange(3, 0)), level.set_field(3, 0, Wall));
+        assert_eq!(Err(EditError::OutOfRange(0, 2)), level.set_field(0, 2, Wall));
+        assert_eq!(&vec![Wall, Player, Wall, Empty, Target, Wall], &level.area);
+        
+        level.resize(4, 3);
+        assert_eq!((4, 3), (level.width, level.height));
+        assert_eq!(Level::from_str("edit", 4, 3, "#@#  .#     ").unwrap()
+                .with_annotation(1, 1, "target").with_annotation(2, 0, "wall"), level);
+        level.resize(2, 2);
+        assert_eq!(Level::from_str("edit", 2, 2, "#@ .").unwrap()
+                .with_annotation(1, 1, "target"), level);
+        
+        assert_eq!(Err(EditError::OutOfRange(0, 3)), level.insert_row(3));
+        assert_eq!(Ok(()), level.insert_row(1));
+        assert_eq!(Level::from_str("edit", 2, 3, "#@   .").unwrap()
+                .with_annotation(1, 2, "target"), level);
+        assert_eq!(Ok(()), level.insert_col(2));
+        assert_eq!(Ok(()), level.insert_col(0));
+        assert_eq!(Level::from_str("edit", 4, 3, " #@       . ").unwrap()
+                .with_annotation(2, 2, "target"), level);
+        assert_eq!(Err(EditError::OutOfRange(5, 0)), level.insert_col(5));
+        
+        assert_eq!(Err(EditError::OutOfRange(0, 3)), level.remove_row(3));
+        assert_eq!(Ok(()), level.remove_row(1));
+        assert_eq!(Level::from_str("edit", 4, 2, " #@   . ").unwrap()
+                .with_annotation(2, 1, "target"), level);
+        assert_eq!(Ok(()), level.remove_col(2));
+        assert_eq!(Level::from_str("edit", 3, 2, " #    ").unwrap(), level);
+        assert_eq!(Err(EditError::OutOfRange(3, 0)), level.remove_col(3));
+        assert_eq!(level.width*level.height, level.area.len());
+    }
+    
+    #[test]
+    fn test_level_par() {
+        let level = Level::from_str("par", 5, 3, "#####\
+                 #@$.#\
+                 #####").unwrap();
+        assert_eq!(None, level.par());
+        let level = level.with_comment("Author: Nobody\nmoves=97 pushes=42");
+        assert_eq!(Some((97, 42)), level.par());
+        let level = level.with_comment("moves=97\npushes=42 moves=x");
+        assert_eq!(None, level.par());
+        let level = level.with_comment("Best: pushes=3 moves=10 by someone");
+        assert_eq!(Some((10, 3)), level.par());
+    }
+    
+    #[test]
+    fn test_level_positions() {
+        let level = Level::from_str("pos", 6, 4,
+            "######\
+             #@$ .#\
+             #.* +#\
+             ######").unwrap();
+        assert_eq!(vec![(2, 1), (2, 2)], level.boxes());
+        assert_eq!(vec![(4, 1), (1, 2), (2, 2), (4, 2)], level.targets());
+        assert_eq!(Some((1, 1)), level.player());
+        assert_eq!(None, Level::from_str("empty", 2, 1, "##").unwrap().player());
+    }
+    
+    #[test]
+    fn test_level_cells() {
+        let level = Level::from_str("cells", 3, 2, "#@#$.#").unwrap();
+        assert_eq!(vec![(0, 0, Wall), (1, 0, Player), (2, 0, Wall),
+                (0, 1, Pack), (1, 1, Target), (2, 1, Wall)],
+                level.cells().collect::<Vec<_>>());
+        assert!(level.cells().all(|(x, y, f)| level.area()[y*level.width() + x] == f));
+    }
+    
+    #[test]
+    fn test_to_string_lines() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #   +.*#\
+             #   $$ #\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(vec![" ######", "#      #", "#   +.*#", "#   $$ #", "#      #",
+                " ######"], level.to_string_lines());
+        assert_eq!(" ######\n#      #\n#   +.*#\n#   $$ #\n#      #\n ######",
+                level.to_string());
+        let level2 = Level::from_lines("git",
+                io::Cursor::new(level.to_string()).lines()).unwrap().unwrap();
+        assert_eq!(level, level2);
+    }
+    
+    #[test]
+    fn test_level_from_rle() {
+        let expected = Level::from_str("rle", 7, 4,
+            "  #####\
+             ###  .#\
+             #@ $  #\
+             #######").unwrap();
+        assert_eq!(Ok(expected), Level::from_rle("rle", "2 5#|3#2 .#|#@ $2 #|7#|"));
+        assert_eq!(Err(WrongField(3, 2)), Level::from_rle("rle", "7#\n#@$.#\n3#5"));
+        assert_eq!(Err(WrongField(1, 1)), Level::from_rle("rle", "5#|#x$.#|5#"));
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_level() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let json = serde_json::to_string(&level).unwrap();
+        assert_eq!(level, serde_json::from_str::<Level>(&json).unwrap());
+        // area doesn't match dimensions
+        let bad_json = json.replace("\"width\":8", "\"width\":9");
+        assert!(serde_json::from_str::<Level>(&bad_json).is_err());
+        let bad_json = json.replace("\"height\":6", "\"height\":4611686018427387904");
+        assert!(serde_json::from_str::<Level>(&bad_json).is_err());
+    }
+    
+    #[test]
+    fn test_level_from_lines() {
+        let input = "  ####\n###  #\n#@$. #\n######\n#\n";
+        let level = Level::from_lines("ragged", io::Cursor::new(input).lines());
+        assert_eq!(Level::from_str("ragged", 6, 5,
+            "  ####\
+             ###  #\
+             #@$. #\
+             ######\
+             #     ").unwrap(), level.unwrap().unwrap());
+        
+        let input = "#####\n#@$.#\n#x###\n";
+        assert_eq!(Err(WrongField(1,2)),
+                Level::from_lines("bad", io::Cursor::new(input).lines()).unwrap());
+        assert_eq!(Err(EmptyLines),
+                Level::from_lines("empty", io::Cursor::new("").lines()).unwrap());
+        // I/O error (invalid UTF-8) is not hidden by truncated level
+        let input: &[u8] = b"#####\n#@$.#\n\xff\n#####\n";
+        let err = Level::from_lines("badio", io::Cursor::new(input).lines()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+    
+    #[test]
+    fn test_check_degenerate_size() {
+        let degenerate = |w, h| {
+            let mut errors = CheckErrors::new();
+            errors.push(DegenerateSize(w, h));
+            Err(errors)
+        };
+        let level = Level::from_str("row", 5, 1, "@$ .#").unwrap();
+        assert_eq!(degenerate(5, 1), level.check());
+        let level = Level::from_str("column", 1, 4, "#@$.").unwrap();
+        assert_eq!(degenerate(1, 4), level.check());
+        assert_eq!(degenerate(0, 0), Level::empty().check());
+        let level = Level::from_str("zero", 0, 3, "").unwrap();
+        assert_eq!(degenerate(0, 3), level.check());
+        assert_eq!("Degenerate size 0x3", DegenerateSize(0, 3).to_string());
+    }
+    
+    #[test]
+    fn test_fill_from() {
+        let level = Level::from_str("fill", 6, 4,
+            "######\
+             #@$ ##\
+             ## #.#\
+             ######").unwrap();
+        let (filled, touch_frames) = level.fill_from(1, 1);
+        assert!(!touch_frames);
+        assert_eq!(vec![(1, 1), (2, 1), (3, 1), (2, 2)], (0..filled.len())
+                .filter(|i| filled[*i]).map(|i| (i % 6, i / 6)).collect::<Vec<_>>());
+        // open level
+        let level = Level::from_str("fill", 4, 3, "#### @ $####").unwrap();
+        let (filled, touch_frames) = level.fill_from(1, 1);
+        assert!(touch_frames);
+        assert_eq!(4, filled.iter().filter(|x| **x).count());
+        // wall at start position
+        let (filled, touch_frames) = level.fill_from(0, 0);
+        assert!(!touch_frames);
+        assert!(filled.iter().all(|x| !*x));
+    }
+    
+    #[test]
+    fn test_check_closed_at_boundary() {
+        // playable area directly abuts walls at boundary of the area
+        let level = Level::from_str("closed", 5, 3, "#####\
+                 #@$.#\
+                 #####").unwrap();
+        assert_eq!(Ok(()), level.check());
+        // empty fields outside walls are not reachable
+        let rows = ["        ", " #####  ", " #@$.#  ", " #####  ", "        "];
+        let level = Level::from_str("closed", 8, 5, &rows.concat()).unwrap();
+        assert_eq!(Ok(()), level.check());
+        // reachable floor at boundary without wall beyond is open
+        let level = Level::from_str("open", 5, 3, "#####\
+                 @$. #\
+                 #####").unwrap();
+        let mut errors = CheckErrors::new();
+        errors.push(LevelOpen);
+        assert_eq!(Err(errors), level.check());
+    }
+    
     #[test]
     fn test_check() {
         let level = Level::from_str("git", 8, 6,
@@ -423,6 +1061,16 @@ mod test {
         errors.push(LockedPackApartWalls(9, 2));
         assert_eq!(Err(errors), level.check());
         
+        // isolated pack on target
+        let level = Level::from_str("git", 10, 6,
+            " #########\
+             #      ###\
+             #@  ...#*#\
+             #   $$$###\
+             #      #  \
+              ##########").unwrap();
+        assert_eq!(Ok(()), level.check());
+        
         // locks
         let level = Level::from_str("git", 8, 6,
             " ###### \
@@ -500,4 +1148,151 @@ mod test {
              ####################").unwrap();
         assert_eq!(Ok(()), level.check());
     }
+    
+    #[test]
+    fn test_stats() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  .*.#\
+             #   $ $#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(20, level.wall_count());
+        assert_eq!(LevelStats{ width: 8, height: 6, walls: 20, packs: 3, targets: 3,
+                empties: 22, reachable_floor: 24 }, level.stats());
+        
+        let level = Level::from_str("git", 10, 6,
+            " #########\
+             #      ###\
+             #@  ...#*#\
+             #   $$$###\
+             #      #  \
+              ##########").unwrap();
+        assert_eq!(LevelStats{ width: 10, height: 6, walls: 32, packs: 4, targets: 4,
+                empties: 20, reachable_floor: 24 }, level.stats());
+    }
+    
+    #[test]
+    fn test_is_rectangle_complete() {
+        let level = Level::from_str("blocky", 6, 7,
+            "######\
+             #    #\
+             # #@ #\
+             # $* #\
+             # .* #\
+             #    #\
+             ######").unwrap();
+        assert!(level.is_rectangle_complete());
+        // rectangle with empty fields around
+        let level = Level::from_str("blocky", 8, 4,
+            "        \
+             ####### \
+             #@ $. # \
+             ####### ").unwrap();
+        assert!(level.is_rectangle_complete());
+        // jagged border
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert!(!level.is_rectangle_complete());
+        let level = Level::from_str("funny", 6, 7,
+            "####  \
+             # .#  \
+             #  ###\
+             #*@  #\
+             #  $ #\
+             #  ###\
+             ####  ").unwrap();
+        assert!(!level.is_rectangle_complete());
+        assert!(!Level::empty().is_rectangle_complete());
+    }
+    
+    #[test]
+    fn test_rows() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(6, level.rows().count());
+        for (y, row) in level.rows().enumerate() {
+            assert_eq!(Some(row), level.row(y));
+            assert_eq!(&level.area()[y*8..(y+1)*8], row);
+        }
+        assert_eq!(Some(&[Wall, Empty, Empty, Empty, Pack, Pack, Pack, Wall][..]),
+                level.row(3));
+        assert_eq!(None, level.row(6));
+        assert_eq!(None, Level::empty().row(0));
+        assert_eq!(0, Level::empty().rows().count());
+    }
+    
+    #[test]
+    fn test_checked() {
+        let level = Level::checked("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ");
+        assert_eq!(Ok(level), Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### "));
+        
+        let result = std::panic::catch_unwind(|| Level::checked("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  .. #\
+             #   $$$#\
+             #      # \
+              ###### "));
+        let msg = result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!("Level git is invalid: Too few targets - required 3.", *msg);
+    }
+    
+    #[test]
+    fn test_check_detailed() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  .*.#\
+             #   $ $#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(Ok(()), level.check());
+        assert_eq!((Ok(()), vec![CheckWarning::PrefilledBox(5, 2)]),
+                level.check_detailed());
+        
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!((Ok(()), vec![]), level.check_detailed());
+        
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #   .*.#\
+             #   $ $#\
+             #      # \
+              ###### ").unwrap();
+        let mut errors = CheckErrors::new();
+        errors.push(NoPlayer);
+        assert_eq!((Err(errors), vec![CheckWarning::PrefilledBox(5, 2)]),
+                level.check_detailed());
+    }
 }
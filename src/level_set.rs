@@ -17,33 +17,373 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
-use std::error::Error;
 use std::io;
-use std::io::{Read,BufRead,BufReader,Seek};
+use std::collections::VecDeque;
+use std::io::{Read,Write,BufRead,BufReader,Seek};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path,PathBuf};
 use quick_xml::Reader as XmlReader;
+use quick_xml::Writer as XmlWriter;
 use quick_xml::events::Event as XmlEvent;
+use quick_xml::events::{BytesDecl,BytesStart,BytesEnd,BytesText};
+use int_enum::IntEnum;
 
 use crate::defs::*;
 
-use crate::Level;
+use crate::{Level,SolveOutcome};
+use crate::solver::SOLVABLE_NODE_LIMIT;
 use Field::*;
 use ParseError::*;
 use XmlParseError::*;
+use BinaryParseError::*;
+
+/// Magic bytes of binary levelset format.
+const BINARY_MAGIC: &[u8; 8] = b"SOKOBAN2";
+
+/// Magic bytes of first version of binary levelset format (levels without identifiers).
+const BINARY_MAGIC_V1: &[u8; 8] = b"SOKOBAN1";
+
+/// Maximal length of string in binary levelset format.
+const BINARY_MAX_STR_LEN: usize = 1 << 16;
+
+/// Maximal number of fields of level in binary levelset format.
+const BINARY_MAX_AREA: usize = 1 << 20;
 
 /// Level result - contains level or parse error.
 pub type LevelResult = Result<Level, LevelParseError>;
 
-fn level_result_set_name(lr: &mut LevelResult, name: &String) {
-    match lr {
-        Ok(l) => l.name = name.clone(),
-        Err(e) => e.name = name.clone(),
+fn write_binary_u32<W: Write>(w: &mut W, v: usize) -> io::Result<()> {
+    w.write_all(&(v as u32).to_le_bytes())
+}
+
+fn write_binary_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_binary_u32(w, s.len())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_binary_u32<R: Read>(r: &mut R) -> io::Result<usize> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf) as usize)
+}
+
+// read exactly len bytes. Buffer grows only when data is really read.
+fn read_binary_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated data"));
+    }
+    Ok(buf)
+}
+
+fn read_binary_str<R: Read>(r: &mut R) -> Result<String, LevelSetError> {
+    let len = read_binary_u32(r)?;
+    if len > BINARY_MAX_STR_LEN {
+        return Err(BadContent.into());
+    }
+    let buf = read_binary_bytes(r, len)?;
+    Ok(String::from_utf8(buf).map_err(|_| BadContent)?)
+}
+
+// kind of line in text levelset.
+#[derive(PartialEq,Eq,Clone,Copy)]
+enum TextLineKind {
+    Comment,
+    Row,
+    // empty line or other text - separates levels and comments
+    Separator,
+}
+
+fn text_line_kind(l: &str) -> TextLineKind {
+    if l.starts_with(';') {
+        TextLineKind::Comment
+    } else if l.trim().is_empty() ||
+            (l.starts_with(|c: char| c.is_alphanumeric()) && !is_rle_row(l)) {
+        TextLineKind::Separator
+    } else {
+        TextLineKind::Row
+    }
+}
+
+// level in text levelset before parsing its rows.
+#[derive(Default)]
+struct TextLevel {
+    rows: Vec<String>,
+    name: Option<String>,
+    annotations: Vec<(usize, usize, String)>,
+    comment: String,
+}
+
+// parse annotation comment in form '; @x,y: text'.
+fn parse_annotation(l: &str) -> Option<(usize, usize, String)> {
+    let rest = l.strip_prefix(';')?.trim_start().strip_prefix('@')?;
+    let (pos, text) = rest.split_once(':')?;
+    let (x, y) = pos.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?, text.trim().to_string()))
+}
+
+// append comment line (without ';') to level comment.
+fn push_comment_line(comment: &mut String, l: &str) {
+    if !comment.is_empty() {
+        comment.push('\n');
+    }
+    comment.push_str(l[1..].trim());
+}
+
+/// Policy of writing levels with parse errors.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum ErrorLevelPolicy {
+    /// Skip levels with errors.
+    Skip,
+    /// Write comment with error in place of level.
+    Comment,
+}
+
+/// Result of validation of single level from levelset.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct LevelValidation {
+    /// Name of level.
+    pub name: String,
+    /// Parse status of level.
+    pub parse: Result<(), ParseError>,
+    /// Result of checking level or None if level has not been parsed.
+    pub check: Option<Result<(), CheckErrors>>,
+    /// Solvability of level or None if level has not been checked successfully.
+    pub solvable: Option<Solvability>,
+}
+
+/// Solvability of level found by limited search.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Solvability {
+    /// Level can be solved.
+    Solvable,
+    /// Level can not be solved.
+    Unsolvable,
+    /// Limit of search has been reached before finding solution.
+    LimitReached,
+}
+
+/// Aggregate statistics of levelset. Levels with errors are excluded from
+/// dimensions and number of packs.
+#[derive(PartialEq,Debug,Clone,Copy)]
+pub struct SetStats {
+    /// Number of valid levels.
+    pub valid_levels: usize,
+    /// Number of levels with errors.
+    pub error_levels: usize,
+    /// Minimal width of level.
+    pub min_width: usize,
+    /// Maximal width of level.
+    pub max_width: usize,
+    /// Mean width of level.
+    pub mean_width: f64,
+    /// Minimal height of level.
+    pub min_height: usize,
+    /// Maximal height of level.
+    pub max_height: usize,
+    /// Mean height of level.
+    pub mean_height: f64,
+    /// Total number of packs in all levels.
+    pub total_packs: usize,
+}
+
+/// Iterator over levels of levelset in text format. Levels are parsed one at
+/// a time, so whole levelset is not kept in memory. Order of levels, their names
+/// and error positions (numbers of levels) are same as in `LevelSet::from_reader`.
+/// Iteration stops at first input/output error that can be retrieved by `take_error`.
+pub struct TextLevelIter<B: BufRead> {
+    lines: io::Lines<B>,
+    charset: FieldCharset,
+    name: String,
+    // lines from split single-line levels and line read ahead
+    line_queue: VecDeque<String>,
+    // block read ahead
+    next_block: Option<(TextLineKind, Vec<String>)>,
+    prev_kind: Option<TextLineKind>,
+    // last level - comments still can be attached to it
+    current: Option<TextLevel>,
+    // comments attached to next level
+    leading: TextLevel,
+    number: usize,
+    error: Option<io::Error>,
+}
+
+impl<B: BufRead> TextLevelIter<B> {
+    /// Create iterator over levels from reader. Fields of levels are denoted by
+    /// characters from given charset.
+    pub fn new(reader: B, charset: FieldCharset) -> Self {
+        let mut iter = TextLevelIter{ lines: reader.lines(), charset, name: String::new(),
+                line_queue: VecDeque::new(), next_block: None, prev_kind: None,
+                current: None, leading: TextLevel::default(), number: 0, error: None };
+        if let Some(l) = iter.next_line() {
+            if l.starts_with(";") {
+                iter.name = l[1..].trim().to_string();
+            }
+        }
+        iter.next_block = iter.read_block();
+        iter
+    }
+    
+    /// Get name of levelset.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Take input/output error that stopped iteration.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+    
+    // return true if text contains only fields and digits (is level row).
+    fn is_row_text(&self, r: &str) -> bool {
+        r.chars().all(|c| c.is_ascii_digit() || self.charset.field(c).is_some())
+    }
+    
+    // read next line. Single-line levels (rows separated by '|') are split into
+    // multiple lines and rows are translated to standard characters. Lines with
+    // other text than level rows are not split.
+    fn next_line(&mut self) -> Option<String> {
+        if let Some(l) = self.line_queue.pop_front() {
+            return Some(l);
+        }
+        if self.error.is_some() { return None; }
+        match self.lines.next()? {
+            Ok(l) if !l.starts_with(";") && l.contains('|') &&
+                    l.split('|').all(|r| self.is_row_text(r)) => {
+                let charset = &self.charset;
+                self.line_queue.extend(l.split('|').filter(|r| !r.is_empty())
+                    .map(|r| charset.translate_row(r.to_string())));
+                self.next_line()
+            }
+            Ok(l) if !l.starts_with(";") => Some(self.charset.translate_row(l)),
+            Ok(l) => Some(l),
+            Err(e) => { self.error = Some(e); None }
+        }
+    }
+    
+    // read block of lines of the same kind. Lines of separators are not kept.
+    fn read_block(&mut self) -> Option<(TextLineKind, Vec<String>)> {
+        let first = self.next_line()?;
+        let kind = text_line_kind(&first);
+        let mut block = vec![];
+        if kind != TextLineKind::Separator {
+            block.push(first);
+        }
+        while let Some(l) = self.next_line() {
+            if text_line_kind(&l) != kind {
+                self.line_queue.push_front(l);
+                break;
+            }
+            if kind != TextLineKind::Separator {
+                block.push(l);
+            }
+        }
+        Some((kind, block))
+    }
+    
+    // parse rows of level and return level or parse error.
+    fn finish(&mut self, tl: TextLevel) -> LevelResult {
+        let number = self.number;
+        self.number += 1;
+        let name = tl.name.unwrap_or_default();
+        let mut level = Level::empty();
+        let mut level_lines = vec![];
+        for l in &tl.rows {
+            // expand run-length encoded row
+            match expand_rle(l) {
+                Ok(row) => {
+                    level.width = level.width.max(row.len());
+                    level_lines.push(row.trim_end().to_string());
+                }
+                Err(pp) => {
+                    return Err(LevelParseError{ number, name,
+                            error: WrongField(pp, level_lines.len()) });
+                }
+            }
+        }
+        
+        level.name = name.clone();
+        level.id = name;
+        level.annotations = tl.annotations;
+        level.comment = tl.comment;
+        level.height = level_lines.len();
+        // construct level
+        level.area = vec![Empty; level.width*level.height];
+        for y in 0..level_lines.len() {
+            level_lines[y].chars().enumerate().for_each(|(x,c)| {
+                level.area[y*level.width + x] = char_to_field(c);
+            });
+        }
+        Ok(level)
+    }
+}
+
+// attach comment block to level. First comment (not annotation) is name of level
+// if level doesn't have name, other comments are metadata of level.
+fn attach_comments(tl: &mut TextLevel, block: &[String]) {
+    for l in block {
+        if let Some(annotation) = parse_annotation(l) {
+            tl.annotations.push(annotation);
+        } else if tl.name.is_none() {
+            tl.name = Some(l[1..].trim().to_string());
+        } else if !l[1..].trim().is_empty() {
+            push_comment_line(&mut tl.comment, l);
+        }
+    }
+}
+
+impl<B: BufRead> Iterator for TextLevelIter<B> {
+    type Item = LevelResult;
+    
+    fn next(&mut self) -> Option<LevelResult> {
+        loop {
+            let (kind, block) = match self.next_block.take() {
+                Some(b) => b,
+                None => {
+                    let tl = self.current.take()?;
+                    return Some(self.finish(tl));
+                }
+            };
+            self.next_block = self.read_block();
+            let next_row = self.next_block.as_ref()
+                    .is_some_and(|(k, _)| *k == TextLineKind::Row);
+            let prev_row = self.prev_kind.replace(kind) == Some(TextLineKind::Row);
+            let done = match kind {
+                TextLineKind::Row => {
+                    let mut tl = std::mem::take(&mut self.leading);
+                    tl.rows = block;
+                    self.current.replace(tl)
+                }
+                // comment block between two levels belongs to previous level
+                // if it doesn't have title yet, otherwise to next level.
+                // Comment blocks not adjacent to any level are skipped.
+                TextLineKind::Comment => {
+                    match self.current.as_mut() {
+                        Some(tl) if prev_row && (!next_row || tl.name.is_none()) => {
+                            attach_comments(tl, &block);
+                            None
+                        }
+                        _ => {
+                            if next_row {
+                                attach_comments(&mut self.leading, &block);
+                            }
+                            self.current.take()
+                        }
+                    }
+                }
+                TextLineKind::Separator => self.current.take(),
+            };
+            if let Some(tl) = done {
+                return Some(self.finish(tl));
+            }
+        }
     }
 }
 
 /// Level set. Contains levels and name of the level set.
 #[derive(PartialEq,Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize,serde::Deserialize))]
 pub struct LevelSet {
     name: String,
     levels: Vec<LevelResult>,
@@ -59,144 +399,429 @@ impl LevelSet {
         &self.levels
     }
     
+    /// Returns true if level set doesn't contain any level.
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    
     /// Returns true if level set has errors.
     pub fn has_errors(&self) -> bool {
         self.levels.iter().find(|lr| lr.is_err()).is_some()
     }
     
+    /// Retain only levels for which predicate returns true.
+    pub fn retain(&mut self, f: impl Fn(&LevelResult) -> bool) {
+        self.levels.retain(f);
+    }
+    
+    /// Return aggregate statistics of levelset.
+    pub fn stats(&self) -> SetStats {
+        let levels: Vec<&Level> = self.levels.iter()
+                .filter_map(|lr| lr.as_ref().ok()).collect();
+        let valid_levels = levels.len();
+        let mean = |sum: usize| if valid_levels != 0 {
+            sum as f64 / valid_levels as f64
+        } else { 0.0 };
+        SetStats{ valid_levels, error_levels: self.levels.len() - valid_levels,
+            min_width: levels.iter().map(|l| l.width).min().unwrap_or(0),
+            max_width: levels.iter().map(|l| l.width).max().unwrap_or(0),
+            mean_width: mean(levels.iter().map(|l| l.width).sum()),
+            min_height: levels.iter().map(|l| l.height).min().unwrap_or(0),
+            max_height: levels.iter().map(|l| l.height).max().unwrap_or(0),
+            mean_height: mean(levels.iter().map(|l| l.height).sum()),
+            total_packs: levels.iter()
+                .map(|l| l.area.iter().filter(|x| x.is_pack()).count()).sum() }
+    }
+    
+    /// Return index of first level (valid or with error) with given name.
+    pub fn position_of(&self, name: &str) -> Option<usize> {
+        self.levels.iter().position(|lr| match lr {
+            Ok(l) => l.name == name,
+            Err(e) => e.name == name,
+        })
+    }
+    
+    /// Give sequential names 'Level N' (N - number of level from 1) to levels
+    /// without title (levels whose name is same as identifier). Identifiers are kept.
+    pub fn rename_levels_from_titles(&mut self) {
+        for (i, lr) in self.levels.iter_mut().enumerate() {
+            if let Ok(l) = lr {
+                if l.name == l.id {
+                    l.name = format!("Level {}", i+1);
+                }
+            }
+        }
+    }
+    
+    /// Consume levelset and return only successfully parsed levels.
+    pub fn into_levels(self) -> Vec<Level> {
+        self.levels.into_iter().filter_map(|lr| lr.ok()).collect()
+    }
+    
+    /// Consume levelset and return all level results (with errors).
+    pub fn into_level_results(self) -> Vec<LevelResult> {
+        self.levels
+    }
+    
+    /// Validate all levels - parse status, check and solvability of levels.
+    /// Search of solution is limited by the same number of expanded nodes as
+    /// `Level::solve_bounded`.
+    pub fn validate(&self) -> Vec<LevelValidation> {
+        self.validate_with_limit(SOLVABLE_NODE_LIMIT)
+    }
+    
+    // validate all levels with given maximal number of expanded nodes per level.
+    fn validate_with_limit(&self, node_limit: usize) -> Vec<LevelValidation> {
+        self.levels.iter().map(|lr| match lr {
+            Ok(l) => {
+                let check = l.check();
+                let solvable = if check.is_ok() {
+                    Some(match l.solve_with_limit(node_limit) {
+                        SolveOutcome::Solved(_) => Solvability::Solvable,
+                        SolveOutcome::Unsolvable => Solvability::Unsolvable,
+                        SolveOutcome::LimitReached => Solvability::LimitReached,
+                    })
+                } else { None };
+                LevelValidation{ name: l.name.clone(), parse: Ok(()),
+                        check: Some(check), solvable }
+            }
+            Err(e) => LevelValidation{ name: e.name.clone(), parse: Err(e.error),
+                        check: None, solvable: None },
+        }).collect()
+    }
+    
     /// Read levelset from string.
-    pub fn from_str(str: &str) -> Result<LevelSet, Box<dyn Error>> {
+    pub fn from_str(str: &str) -> Result<LevelSet, LevelSetError> {
         Self::from_reader(&mut io::Cursor::new(str.as_bytes()))
     }
     /// Read levelset from file.
     pub fn from_file<P: AsRef<Path>>(path: P) ->
-                    Result<LevelSet, Box<dyn Error>> {
+                    Result<LevelSet, LevelSetError> {
         let f = File::open(path)?;
         Self::from_reader(&mut BufReader::new(f))
     }
+    /// Read levelsets from files and concatenate them into one levelset with given name.
+    /// Levels with errors are preserved.
+    pub fn from_files<I: IntoIterator<Item=PathBuf>>(paths: I, name: &str) ->
+                    Result<LevelSet, LevelSetError> {
+        Self::from_sources(paths, name, Self::from_file)
+    }
+    
+    // read levelsets from sources by using load function and concatenate them.
+    fn from_sources<T, I, F>(sources: I, name: &str, load: F) ->
+                    Result<LevelSet, LevelSetError>
+            where I: IntoIterator<Item=T>, F: Fn(T) -> Result<LevelSet, LevelSetError> {
+        let mut lset = LevelSet{ name: name.to_string(), levels: vec![] };
+        for source in sources {
+            for mut lr in load(source)?.levels {
+                if let Err(e) = &mut lr {
+                    e.number = lset.levels.len();
+                }
+                lset.levels.push(lr);
+            }
+        }
+        Ok(lset)
+    }
+    
+    /// Read levelset from file. A format is chosen by extension of the file:
+    /// '.slc' and '.xml' - XML format, '.sok' and '.txt' - text format,
+    /// '.json' - JSON format (only with `serde` feature). For other extensions
+    /// a format is determined by content of the file like in `from_file`.
+    pub fn from_path_typed<P: AsRef<Path>>(path: P) ->
+                    Result<LevelSet, LevelSetError> {
+        let ext = path.as_ref().extension().and_then(|e| e.to_str())
+                .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("slc") | Some("xml") => {
+                let f = File::open(path)?;
+                Self::read_from_xml(&mut BufReader::new(f), &FieldCharset::default())
+            }
+            Some("sok") | Some("txt") => {
+                let f = File::open(path)?;
+                Self::read_from_text(&mut BufReader::new(f), &FieldCharset::default())
+            }
+            Some("json") => {
+                let f = File::open(path)?;
+                Self::read_from_json(&mut BufReader::new(f))
+            }
+            _ => Self::from_file(path),
+        }
+    }
     /// Read levelset from reader.
     pub fn from_reader<B: BufRead + Read + Seek>(reader: &mut B) ->
-                    Result<LevelSet, Box<dyn Error>> {
+                    Result<LevelSet, LevelSetError> {
+        Self::from_reader_with_charset(reader, &FieldCharset::default())
+    }
+    
+    /// Read levelset from reader. Fields of levels are denoted by characters
+    /// from given charset.
+    pub fn from_reader_with_charset<B: BufRead + Read + Seek>(reader: &mut B,
+                    charset: &FieldCharset) -> Result<LevelSet, LevelSetError> {
         let mut first_bytes = [0;5];
         let readed = reader.read(&mut first_bytes)?;
         reader.seek(io::SeekFrom::Start(0))?;
         if readed == 5 && (&first_bytes == b"<?xml") {
             // if xml
-            Self::read_from_xml(reader)
+            Self::read_from_xml(reader, charset)
         } else {
             // if text
-            Self::read_from_text(reader)
+            Self::read_from_text(reader, charset)
         }
     }
     
-    fn read_from_text<B: BufRead + Read + Seek>(reader: &mut B) ->
-                    Result<LevelSet, Box<dyn Error>> {
-        let mut lines = reader.lines();
-        
-        let mut lset = LevelSet{ name: String::new(), levels: vec![] };
-        if let Some(rl) = lines.next() {
-            let l = rl?; // handle error
-            if l.starts_with(";") {
-                lset.name = l[1..].trim().to_string();
-            }
-        }
-        // skip comments and spaces
-        let mut first_empty_line = false;
-        let mut lev_lines = lines.skip_while(|rl| {
-            if let Ok(l) = rl {
-                if l.starts_with(";") { return true; }
-                else if l.len()!=0 {
-                    if let Some(c) = l.chars().next() {
-                        // skip some text
-                        if c.is_alphanumeric() { return true; }
+    /// Read levelset from reader like `from_reader`, but return error if
+    /// levelset doesn't contain any level.
+    pub fn from_reader_nonempty<B: BufRead + Read + Seek>(reader: &mut B) ->
+                    Result<LevelSet, LevelSetError> {
+        let lset = Self::from_reader(reader)?;
+        if lset.is_empty() {
+            Err(LevelSetError::EmptyLevelSet)
+        } else { Ok(lset) }
+    }
+    
+    /// Write levelset in binary format. Binary format contains: magic bytes,
+    /// name of levelset, number of levels and levels. Every level contains
+    /// name, identifier, width, height, packed area (two fields in byte), comment
+    /// and annotations (number of annotations, then x, y and text of every one).
+    /// Strings are prefixed by length. All numbers are 32-bit little endian.
+    pub fn write_binary<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(BINARY_MAGIC)?;
+        write_binary_str(w, &self.name)?;
+        write_binary_u32(w, self.levels.len())?;
+        for lr in &self.levels {
+            match lr {
+                Ok(l) => {
+                    w.write_all(&[0])?;
+                    write_binary_str(w, &l.name)?;
+                    write_binary_str(w, &l.id)?;
+                    write_binary_u32(w, l.width)?;
+                    write_binary_u32(w, l.height)?;
+                    let packed: Vec<u8> = l.area.chunks(2).map(|c|
+                        c[0].int_value() | (c.get(1).map(|x| x.int_value())
+                                .unwrap_or_default()<<4)).collect();
+                    w.write_all(&packed)?;
+                    write_binary_str(w, &l.comment)?;
+                    write_binary_u32(w, l.annotations.len())?;
+                    for (x, y, text) in &l.annotations {
+                        write_binary_u32(w, *x)?;
+                        write_binary_u32(w, *y)?;
+                        write_binary_str(w, text)?;
                     }
-                } else if !first_empty_line && l.trim().len() == 0 {
-                    first_empty_line = true;
-                    return true
+                }
+                Err(e) => {
+                    w.write_all(&[1])?;
+                    write_binary_str(w, &e.name)?;
+                    write_binary_u32(w, e.number)?;
+                    let (kind, x, y) = match e.error {
+                        EmptyLines => (0, 0, 0),
+                        WrongField(x, y) => (1, x, y),
+                        WrongSize(x, y) => (2, x, y),
+                    };
+                    w.write_all(&[kind])?;
+                    write_binary_u32(w, x)?;
+                    write_binary_u32(w, y)?;
                 }
             }
+        }
+        Ok(())
+    }
+    
+    /// Read levelset from binary format. First version of format (without
+    /// identifiers, comments and annotations of levels) is also accepted - then
+    /// identifier is name of level.
+    pub fn read_binary<R: Read>(r: &mut R) -> Result<LevelSet, LevelSetError> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        let with_ids = if &magic == BINARY_MAGIC {
+            true
+        } else if &magic == BINARY_MAGIC_V1 {
             false
-        }).filter(|rl| {
-            if let Ok(l) = rl {
-                l.trim().len() != 0
-            } else { false }
-        });
-        
-        // parse levels
-        let mut level_name_first = false;
-        let mut level_name = String::new();
-        let mut l;
-        if let Some(rl) = lev_lines.next() {
-            l = rl?; // handle error and get line
-            'a: loop {
-                if l.starts_with(";") {
-                    // comments
-                    level_name = l[1..].trim().to_string();
-                    if lset.levels.len() == 0 {
-                        level_name_first = true;
+        } else {
+            return Err(BadMagic.into());
+        };
+        let mut lset = LevelSet{ name: read_binary_str(r)?, levels: vec![] };
+        let levels_num = read_binary_u32(r)?;
+        for _ in 0..levels_num {
+            let mut tag = [0; 1];
+            r.read_exact(&mut tag)?;
+            match tag[0] {
+                0 => {
+                    let name = read_binary_str(r)?;
+                    let id = if with_ids { read_binary_str(r)? } else { name.clone() };
+                    let width = read_binary_u32(r)?;
+                    let height = read_binary_u32(r)?;
+                    let size = width.checked_mul(height)
+                            .filter(|size| *size <= BINARY_MAX_AREA).ok_or(BadContent)?;
+                    let packed = read_binary_bytes(r, (size+1)>>1)?;
+                    let mut area = Vec::with_capacity(size);
+                    for i in 0..size {
+                        let v = (packed[i>>1] >> ((i&1)<<2)) & 15;
+                        area.push(Field::from_int(v).map_err(|_| BadContent)?);
                     }
-                    if !level_name_first {
-                        if let Some(level_result) = lset.levels.last_mut() {
-                            level_result_set_name(level_result, &level_name);
+                    let mut comment = String::new();
+                    let mut annotations = vec![];
+                    if with_ids {
+                        comment = read_binary_str(r)?;
+                        let annotations_num = read_binary_u32(r)?;
+                        for _ in 0..annotations_num {
+                            let x = read_binary_u32(r)?;
+                            let y = read_binary_u32(r)?;
+                            annotations.push((x, y, read_binary_str(r)?));
                         }
                     }
-                    loop {
-                        if let Some(rl) = lev_lines.next() {
-                            l = rl?;
-                            // skip other comments
-                            if !l.starts_with(";") { break; }
-                        } else { break 'a; }
+                    lset.levels.push(Ok(Level{ id, name, width, height,
+                            area, annotations, comment }));
+                }
+                1 => {
+                    let name = read_binary_str(r)?;
+                    let number = read_binary_u32(r)?;
+                    let mut kind = [0; 1];
+                    r.read_exact(&mut kind)?;
+                    let x = read_binary_u32(r)?;
+                    let y = read_binary_u32(r)?;
+                    let error = match kind[0] {
+                        0 => EmptyLines,
+                        1 => WrongField(x, y),
+                        2 => WrongSize(x, y),
+                        _ => { return Err(BadContent.into()); }
+                    };
+                    lset.levels.push(Err(LevelParseError{ number, name, error }));
+                }
+                _ => { return Err(BadContent.into()); }
+            }
+        }
+        Ok(lset)
+    }
+    
+    /// Write levelset in XML format (SokobanLevels). Levels with errors are written
+    /// as comments.
+    pub fn write_to_xml<W: Write>(&self, w: &mut W) -> Result<(), LevelSetError> {
+        self.write_to_xml_with_policy(w, ErrorLevelPolicy::Comment)
+    }
+    
+    /// Write levelset in XML format (SokobanLevels). Levels with errors are
+    /// written according to policy.
+    pub fn write_to_xml_with_policy<W: Write>(&self, w: &mut W,
+                    policy: ErrorLevelPolicy) -> Result<(), LevelSetError> {
+        let mut writer = XmlWriter::new_with_indent(w, b' ', 2);
+        writer.write_event(XmlEvent::Decl(BytesDecl::new(b"1.0", Some(b"utf-8"), None)))?;
+        writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"SokobanLevels")))?;
+        writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"Title")))?;
+        writer.write_event(XmlEvent::Text(BytesText::from_plain_str(&self.name)))?;
+        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"Title")))?;
+        writer.write_event(XmlEvent::Start(
+                    BytesStart::borrowed_name(b"LevelCollection")))?;
+        for lr in &self.levels {
+            match lr {
+                Ok(l) => {
+                    let mut elem = BytesStart::borrowed_name(b"Level");
+                    elem.push_attribute(("Id", l.id.as_str()));
+                    elem.push_attribute(("Width", l.width.to_string().as_str()));
+                    elem.push_attribute(("Height", l.height.to_string().as_str()));
+                    writer.write_event(XmlEvent::Start(elem))?;
+                    if l.name != l.id {
+                        writer.write_event(XmlEvent::Start(
+                                BytesStart::borrowed_name(b"Title")))?;
+                        writer.write_event(XmlEvent::Text(
+                                BytesText::from_plain_str(&l.name)))?;
+                        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"Title")))?;
+                    }
+                    if !l.comment.is_empty() {
+                        writer.write_event(XmlEvent::Start(
+                                BytesStart::borrowed_name(b"Comment")))?;
+                        writer.write_event(XmlEvent::Text(
+                                BytesText::from_plain_str(&l.comment)))?;
+                        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"Comment")))?;
                     }
-                } else {
-                    // level area
-                    let mut level = Level::empty();
-                    let mut error = None;
-                    let mut level_lines = vec![];
-                    
-                    level.name = level_name.clone();
-                    let mut end = false;
-                    loop {
-                        if l.starts_with(";") { break; }
-                        level.width = level.width.max(l.len());
-                        if let Some(pp) = l.chars().position(is_not_field) {
-                            // generate error
-                            error = Some(LevelParseError{
-                                number: lset.levels.len(), name: level_name.clone(),
-                                error: WrongField(pp, level_lines.len()) })
-                        }
-                        level_lines.push(l.trim_end().to_string());
-                        if let Some(rl) = lev_lines.next() {
-                            l = rl?;
-                        } else {
-                            end = true;
-                            break; }
+                    for (x, y, text) in &l.annotations {
+                        let mut elem = BytesStart::borrowed_name(b"Annotation");
+                        elem.push_attribute(("X", x.to_string().as_str()));
+                        elem.push_attribute(("Y", y.to_string().as_str()));
+                        writer.write_event(XmlEvent::Start(elem))?;
+                        writer.write_event(XmlEvent::Text(BytesText::from_plain_str(text)))?;
+                        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"Annotation")))?;
                     }
-                    
-                    if error == None {
-                        level.height = level_lines.len();
-                        // construct level
-                        level.area = vec![Empty; level.width*level.height];
-                        for y in 0..level_lines.len() {
-                            level_lines[y].chars().enumerate().for_each(|(x,c)| {
-                                level.area[y*level.width + x] = char_to_field(c);
-                            });
-                        }
-                        lset.levels.push(Ok(level));
-                    } else {
-                        lset.levels.push(Err(error.unwrap()));
+                    for row in l.area.chunks(l.width.max(1)) {
+                        let line: String = row.iter().map(|f| f.to_char()).collect();
+                        let line = line.trim_end();
+                        // empty line must contain some character
+                        let line = if line.is_empty() { " " } else { line };
+                        writer.write_event(XmlEvent::Start(BytesStart::borrowed_name(b"L")))?;
+                        writer.write_event(XmlEvent::Text(BytesText::from_plain_str(line)))?;
+                        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"L")))?;
+                    }
+                    writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"Level")))?;
+                }
+                Err(e) => {
+                    if policy == ErrorLevelPolicy::Comment {
+                        // comment can not contain '--'
+                        let text = format!(" error: {} ", e).replace("--", "- -");
+                        writer.write_event(XmlEvent::Comment(
+                                BytesText::from_escaped_str(&text)))?;
                     }
-                    
-                    if end { break; }
                 }
             }
         }
-        
-        // parse levels
+        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"LevelCollection")))?;
+        writer.write_event(XmlEvent::End(BytesEnd::borrowed(b"SokobanLevels")))?;
+        Ok(())
+    }
+    
+    /// Write levelset in text format. Name of levelset is written as first comment.
+    /// Every level is followed by comment with its name and annotations.
+    /// Levels with errors are written as comments.
+    pub fn write_to_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "; {}", self.name)?;
+        for lr in &self.levels {
+            writeln!(w)?;
+            match lr {
+                Ok(l) => {
+                    for line in l.to_string_lines() {
+                        writeln!(w, "{}", line)?;
+                    }
+                    writeln!(w, "; {}", l.name)?;
+                    for line in l.comment.lines() {
+                        writeln!(w, "; {}", line)?;
+                    }
+                    for (x, y, text) in &l.annotations {
+                        writeln!(w, "; @{},{}: {}", x, y, text)?;
+                    }
+                }
+                Err(e) => writeln!(w, "; error: {}", e)?,
+            }
+        }
+        Ok(())
+    }
+    
+    #[cfg(feature = "serde")]
+    fn read_from_json<B: BufRead>(reader: &mut B) -> Result<LevelSet, LevelSetError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+    
+    #[cfg(not(feature = "serde"))]
+    fn read_from_json<B: BufRead>(_reader: &mut B) -> Result<LevelSet, LevelSetError> {
+        Err(LevelSetError::UnsupportedFormat("json".to_string()))
+    }
+    
+    fn read_from_text<B: BufRead + Read + Seek>(reader: &mut B,
+                    charset: &FieldCharset) -> Result<LevelSet, LevelSetError> {
+        let mut iter = TextLevelIter::new(reader, charset.clone());
+        let mut lset = LevelSet{ name: iter.name().to_string(), levels: vec![] };
+        lset.levels.extend(&mut iter);
+        if let Some(e) = iter.take_error() {
+            return Err(e.into());
+        }
         Ok(lset)
     }
     
-    fn read_from_xml<B: BufRead + Read + Seek>(reader: &mut B) ->
-                    Result<LevelSet, Box<dyn Error>> {
+    /// Return iterator over levels of levelset in text format, which parses
+    /// one level at a time. Levels and errors are same as in `from_reader`.
+    pub fn iter_levels<B: BufRead>(reader: B) -> TextLevelIter<B> {
+        TextLevelIter::new(reader, FieldCharset::default())
+    }
+    
+    fn read_from_xml<B: BufRead + Read + Seek>(reader: &mut B,
+                    charset: &FieldCharset) -> Result<LevelSet, LevelSetError> {
         let mut lset = LevelSet{ name: String::new(), levels: vec![] };
         
         let mut reader = XmlReader::from_reader(reader);
@@ -205,6 +830,9 @@ impl LevelSet {
         let mut in_level_collection = false;
         let mut in_level_line = false;
         let mut in_title = false;
+        let mut in_level_title = false;
+        let mut in_level_comment = false;
+        let mut in_level_annotation = false;
         
         loop {
             let mut in_level = false;
@@ -218,25 +846,27 @@ impl LevelSet {
                     match e.name() {
                         b"SokobanLevels" => {
                             if in_levels {
-                                return Err(Box::new(BadStructure));
+                                return Err(BadStructure.into());
                             }
                             in_levels = true;
                         }
                         b"Title" => {
                             if in_level_collection {
-                                return Err(Box::new(BadStructure));
+                                return Err(BadStructure.into());
                             }
                             in_title = true;
                         }
                         b"LevelCollection" => {
                             if !in_levels {
-                                return Err(Box::new(BadStructure));
+                                return Err(BadStructure.into());
                             }
                             in_level_collection = true;
                         }
                         b"Level" => {
-                            if !in_level_collection {
-                                return Err(Box::new(BadStructure));
+                            // levels can be directly in SokobanLevels
+                            // (implicit level collection)
+                            if !in_level_collection && !in_levels {
+                                return Err(BadStructure.into());
                             }
                             for ra in e.attributes() {
                                 if let Ok(attr) = ra {
@@ -276,7 +906,7 @@ impl LevelSet {
                         in_title = false;
                     }
                 }
-                Err(e) => { return Err(Box::new(e)); }
+                Err(e) => { return Err(e.into()); }
                 Ok(XmlEvent::Eof) => break,
                 _ => {}
             }
@@ -285,6 +915,7 @@ impl LevelSet {
                 let mut level = Level::empty();
                 if let Some(lid) = level_id {
                     level.name = lid.clone();
+                    level.id = lid;
                 }
                 level.width = level_width;
                 level.height = level_height;
@@ -298,6 +929,28 @@ impl LevelSet {
                                 b"L" => {
                                     in_level_line = true;
                                 }
+                                b"Title" => {
+                                    in_level_title = true;
+                                }
+                                b"Comment" => {
+                                    in_level_comment = true;
+                                }
+                                b"Annotation" => {
+                                    let (mut x, mut y) = (0, 0);
+                                    for attr in e.attributes().flatten() {
+                                        match attr.key {
+                                            b"X" => { x = attr.
+                                                unescape_and_decode_value(&reader)?
+                                                .parse()?; },
+                                            b"Y" => { y = attr.
+                                                unescape_and_decode_value(&reader)?
+                                                .parse()?; },
+                                            _ => {},
+                                        }
+                                    }
+                                    level.annotations.push((x, y, String::new()));
+                                    in_level_annotation = true;
+                                }
                                 _ => {}
                             }
                         }
@@ -305,18 +958,29 @@ impl LevelSet {
                             match e.name() {
                                 b"Level" => { break; }
                                 b"L" => { in_level_line = false; }
+                                b"Title" => { in_level_title = false; }
+                                b"Comment" => { in_level_comment = false; }
+                                b"Annotation" => { in_level_annotation = false; }
                                 _ => {}
                             }
                         }
-                        Err(e) => { return Err(Box::new(e)); }
+                        Err(e) => { return Err(e.into()); }
                         Ok(XmlEvent::Text(e)) => {
-                            if in_level_line {
+                            if in_level_title {
+                                level.name = e.unescape_and_decode(&reader)?;
+                            } else if in_level_comment {
+                                level.comment = e.unescape_and_decode(&reader)?;
+                            } else if in_level_annotation {
+                                if let Some(annotation) = level.annotations.last_mut() {
+                                    annotation.2 = e.unescape_and_decode(&reader)?;
+                                }
+                            } else if in_level_line {
                                 if level.height != 0 && level_lines.len() == level.height {
                                     break; // do not fetch next lines
                                 }
                                 
                                 // if in_level_line
-                                let l = e.unescape_and_decode(&reader)?;
+                                let l = charset.translate_row(e.unescape_and_decode(&reader)?);
                                 if level.width != 0 && l.len() > level.width {
                                     level_lines.push(l.trim_end()[..level.width].to_string());
                                 } else {
@@ -433,6 +1097,9 @@ mod test {
                      ########").unwrap()),
             ] };
         assert_eq!(exp_lsr, lsr);
+        assert_eq!(Some(2), lsr.position_of("third"));
+        assert_eq!(Some(0), lsr.position_of("first"));
+        assert_eq!(None, lsr.position_of("fourth"));
         
         let input_str = r##"; Microban IV
 
@@ -575,63 +1242,927 @@ Microban IV (102 puzzles, August 2010) This set includes a series of alphabet
     }
     
     #[test]
-    fn test_read_from_xml() {
-        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
-<SokobanLevels xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="SokobanLev.xsd">
-  <Title>Microban</Title>
-  <Email>sasquatch@bentonrea.com</Email>
-  <Url>http://users.bentonrea.com/~sasquatch/sokoban/</Url>
-  <LevelCollection Copyright="David W Skinner" MaxWidth="30" MaxHeight="17">
-    <Level Id="funny" Width="6" Height="7">
-      <L>####</L>
-      <L># .#</L>
-      <L>#  ###</L>
-      <L>#*@  #</L>
-      <L>#  $ #</L>
-      <L>#  ###</L>
-      <L>####</L>
-    </Level>
-    <Level Id="blocky" Width="6" Height="7">
-      <L>######</L>
-      <L>#    #</L>
-      <L># #@ #</L>
-      <L># $* #</L>
-      <L># .* #</L>
-      <L>#    #</L>
-      <L>######</L>
-    </Level>
-    <Level Id="harder" Width="9" Height="6">
-      <L>  ####</L>
-      <L>###  ####</L>
-      <L>#     $ #</L>
-      <L># #  #$ #</L>
-      <L># . .#@ #</L>
-      <L>#########</L>
-    </Level>
-  </LevelCollection>
-</SokobanLevels>"##;
+    fn test_read_from_text_trailing() {
+        let input_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+#####
+#@$.#
+#####
+; first
+
+#######
+#@ $ .#
+#######
+; second
+
+; End of levelset
+; Thanks for playing
+
+End of file
+Author: David W Skinner
+
+"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(2, lsr.levels().len());
+        assert!(!lsr.has_errors());
+        assert_eq!("first", lsr.levels()[0].as_ref().unwrap().name());
+        assert_eq!("second", lsr.levels()[1].as_ref().unwrap().name());
+    }
+    
+    #[test]
+    fn test_read_from_text_mixed_titles() {
+        let input_str = r##"; Mixed
+
+; first
+#####
+#@$.#
+#####
+
+#######
+#@ $ .#
+#######
+; second
+; Author: Nobody
+
+; third
+#####
+#.$@#
+#####
+; fourth
+######
+#.$ @#
+######
+
+; End of levelset
+"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        let names: Vec<_> = lsr.levels().iter()
+                .map(|lr| lr.as_ref().unwrap().name().to_string()).collect();
+        assert_eq!(vec!["first", "second", "third", "fourth"], names);
+        assert_eq!("Author: Nobody", lsr.levels()[1].as_ref().unwrap().comment());
+        assert_eq!("", lsr.levels()[3].as_ref().unwrap().comment());
         
-            let lsr = LevelSet::from_str(input_str).unwrap();
-            let exp_lsr = LevelSet{ name: "Microban".to_string(),
-            levels: vec![
-                Ok(Level::from_str("funny", 6, 7,
-                    "####  \
-                     # .#  \
-                     #  ###\
-                     #*@  #\
-                     #  $ #\
-                     #  ###\
-                     ####  ").unwrap()),
-                Ok(Level::from_str("blocky", 6, 7,
-                    "######\
-                     #    #\
-                     # #@ #\
-                     # $* #\
-                     # .* #\
-                     #    #\
-                     ######").unwrap()),
-                Ok(Level::from_str("harder", 9, 6,
-                    "  ####   \
+        // trailing titles without empty lines between levels
+        let lsr = LevelSet::from_str("; Dense
+#####
+#@$.#
+#####
+; first
+#####
+#.$@#
+#####
+; second
+").unwrap();
+        assert_eq!("first", lsr.levels()[0].as_ref().unwrap().name());
+        assert_eq!("second", lsr.levels()[1].as_ref().unwrap().name());
+    }
+    
+    #[test]
+    fn test_read_from_text_with_charset() {
+        let input_str = r##"; Alternate
+
+--#####
+###--.#
+#p-b--#
+#######
+; first
+
+######|#.bP_#|######
+; second
+"##;
+        let lset = LevelSet::from_reader_with_charset(
+                &mut io::Cursor::new(input_str.as_bytes()), &FieldCharset::extended()).unwrap();
+        assert_eq!(LevelSet{ name: "Alternate".to_string(), levels: vec![
+            Ok(Level::from_str("first", 7, 4,
+                "  #####\
+                 ###  .#\
+                 #@ $  #\
+                 #######").unwrap()),
+            Ok(Level::from_str("second", 6, 3,
+                "######\
+                 #.$+ #\
+                 ######").unwrap()),
+            ] }, lset);
+        // default charset doesn't accept alternate symbols
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert!(lset.has_errors());
+    }
+    
+    #[test]
+    fn test_iter_levels() {
+        let input_str = r##"; Stream
+
+; Copyright: Nobody
+
+; first
+#####
+#@$.#
+#####
+
+#####
+#@x.#
+#####
+; bad
+
+#####|#.$@#|#####
+; third
+; @1,1: target
+"##;
+        let mut iter = LevelSet::iter_levels(io::Cursor::new(input_str.as_bytes()));
+        assert_eq!("Stream", iter.name());
+        let levels: Vec<_> = (&mut iter).collect();
+        assert!(iter.take_error().is_none());
+        assert_eq!("first", levels[0].as_ref().unwrap().name());
+        assert_eq!(LevelSet::from_str(input_str).unwrap().levels(), &levels);
+        assert_eq!(Err(LevelParseError{ number: 1, name: "bad".to_string(),
+                    error: WrongField(2, 1) }), levels[1]);
+        assert_eq!(&vec![(1, 1, "target".to_string())],
+                levels[2].as_ref().unwrap().annotations());
+        assert!(iter.next().is_none());
+    }
+    
+    #[test]
+    fn test_empty_levelset() {
+        let text_str = r##"; Microban
+
+; Copyright: David W Skinner
+; Only comments
+"##;
+        let xml_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Microban</Title>
+  <LevelCollection>
+  </LevelCollection>
+</SokobanLevels>"##;
+        for input_str in &[text_str, xml_str] {
+            let lsr = LevelSet::from_str(input_str).unwrap();
+            assert!(lsr.is_empty());
+            assert_eq!("Microban", lsr.name());
+            match LevelSet::from_reader_nonempty(&mut io::Cursor::new(input_str.as_bytes())) {
+                Err(LevelSetError::EmptyLevelSet) => {}
+                r => panic!("Unexpected result {:?}", r),
+            }
+        }
+        
+        let text_str = r##"; Microban
+
+#####
+#@$.#
+#####
+; first
+"##;
+        let lsr = LevelSet::from_reader_nonempty(
+                    &mut io::Cursor::new(text_str.as_bytes())).unwrap();
+        assert!(!lsr.is_empty());
+    }
+    
+    #[test]
+    fn test_read_from_text_single_line() {
+        let input_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+   #####|####@  #|#  $*. #|#     ##|#  #####|####
+; first
+
+########|#  #   #|# $$*. #|# .  . #|# .*$$@#|#   #  #|########|
+; second
+
+Moves: 97 | Pushes: 42
+"##;
+        let multi_input_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+   #####
+####@  #
+#  $*. #
+#     ##
+#  #####
+####
+; first
+
+########
+#  #   #
+# $$*. #
+# .  . #
+# .*$$@#
+#   #  #
+########
+; second
+"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(2, lsr.levels().len());
+        assert!(!lsr.has_errors());
+        assert_eq!(LevelSet::from_str(multi_input_str).unwrap(), lsr);
+    }
+    
+    #[test]
+    fn test_read_annotations() {
+        let input_str = r##"; Tutorial
+
+; Copyright: Unknown
+
+#####
+#@$.#
+#####
+; push
+; @2,1: Push this box to the right.
+; @ 3 , 1 : Target
+
+#######
+#@ $ .#
+#######
+; walk
+; @x,1: Not an annotation
+"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(2, lsr.levels().len());
+        let level = lsr.levels()[0].as_ref().unwrap();
+        assert_eq!("push", level.name());
+        assert_eq!(&vec![(2, 1, "Push this box to the right.".to_string()),
+                (3, 1, "Target".to_string())], level.annotations());
+        let level = lsr.levels()[1].as_ref().unwrap();
+        assert_eq!("walk", level.name());
+        assert!(level.annotations().is_empty());
+        
+        let level = Level::from_str("simple", 5, 3,
+            "#####\
+             #@$.#\
+             #####").unwrap().with_annotation(2, 1, "Push this box to the right.");
+        assert_eq!(lsr.levels()[0].as_ref().unwrap().annotations()[0..1],
+                level.annotations()[..]);
+        // annotations follow transforms
+        assert_eq!(&vec![(2, 1, "Push this box to the right.".to_string())],
+                level.mirror().annotations());
+        assert_eq!(&vec![(1, 2, "Push this box to the right.".to_string())],
+                level.rotate().annotations());
+    }
+    
+    #[test]
+    fn test_retain() {
+        let input_str = r##"; Microban IV
+
+; Copyright: David W Skinner
+
+; first
+   #####
+####@  #
+#  $*. #
+#     ##
+#  #####
+####
+
+; second
+      #####
+   ####   #
+####  $*. #
+#  $*.  b##
+# @   #####
+#  ####
+####
+
+; third
+########
+#  #   #
+# $$*. #
+# .  . #
+# .*$$@#
+#   #  #
+########
+"##;
+        let mut lsr = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(3, lsr.levels().len());
+        lsr.retain(|lr| lr.is_ok());
+        assert_eq!(2, lsr.levels().len());
+        assert!(!lsr.has_errors());
+        assert_eq!("first", lsr.levels()[0].as_ref().unwrap().name());
+        assert_eq!("third", lsr.levels()[1].as_ref().unwrap().name());
+        
+        lsr.retain(|lr| lr.as_ref().map(|l| l.height() < 7).unwrap_or(false));
+        assert_eq!(1, lsr.levels().len());
+        assert_eq!("first", lsr.levels()[0].as_ref().unwrap().name());
+    }
+    
+    #[test]
+    fn test_into_levels() {
+        let input_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+; funny
+
+####
+#.x#
+####
+; bad
+
+#####
+#@$.#
+#####
+; simple
+"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        let valid_count = lsr.levels().iter().filter(|lr| lr.is_ok()).count();
+        let levels = lsr.into_levels();
+        assert_eq!(2, valid_count);
+        assert_eq!(valid_count, levels.len());
+        assert_eq!("funny", levels[0].name());
+        assert_eq!("simple", levels[1].name());
+        
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        let lrs = lsr.into_level_results();
+        assert_eq!(3, lrs.len());
+        assert!(lrs[1].is_err());
+    }
+    
+    #[test]
+    fn test_from_sources() {
+        let input_strs = [r##"; Microban
+
+; Copyright: David W Skinner
+
+####
+#.x#
+####
+; bad
+
+#####
+#@$.#
+#####
+; simple
+"##, r##"; Other
+
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+; funny
+"##];
+        let lsr = LevelSet::from_sources(input_strs.iter(), "All",
+                    |s| LevelSet::from_str(s)).unwrap();
+        assert_eq!("All", lsr.name());
+        assert_eq!(3, lsr.levels().len());
+        match &lsr.levels()[0] {
+            Err(e) => assert_eq!(("bad", 0), (e.name.as_str(), e.number)),
+            r => panic!("Unexpected result {:?}", r),
+        }
+        assert_eq!("simple", lsr.levels()[1].as_ref().unwrap().name());
+        assert_eq!("funny", lsr.levels()[2].as_ref().unwrap().name());
+        
+        match LevelSet::from_files(vec![PathBuf::from("/nonexistent/levelset.sok")], "x") {
+            Err(LevelSetError::Io(e)) => assert_eq!(io::ErrorKind::NotFound, e.kind()),
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+    
+    #[test]
+    fn test_stats() {
+        let text_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+; funny
+
+####
+#.x#
+####
+; bad
+
+  ####
+###  ####
+#     $ #
+# #  #$ #
+# . .#@ #
+#########
+; harder
+"##;
+        let xml_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Microban</Title>
+  <LevelCollection>
+    <Level Id="funny" Width="6" Height="7">
+      <L>####</L>
+      <L># .#</L>
+      <L>#  ###</L>
+      <L>#*@  #</L>
+      <L>#  $ #</L>
+      <L>#  ###</L>
+      <L>####</L>
+    </Level>
+    <Level Id="bad" Width="4" Height="3">
+      <L>####</L>
+      <L>#.x#</L>
+      <L>####</L>
+    </Level>
+    <Level Id="harder" Width="9" Height="6">
+      <L>  ####</L>
+      <L>###  ####</L>
+      <L>#     $ #</L>
+      <L># #  #$ #</L>
+      <L># . .#@ #</L>
+      <L>#########</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        let exp_stats = SetStats{ valid_levels: 2, error_levels: 1,
+                min_width: 6, max_width: 9, mean_width: 7.5,
+                min_height: 6, max_height: 7, mean_height: 6.5, total_packs: 4 };
+        assert_eq!(exp_stats, LevelSet::from_str(text_str).unwrap().stats());
+        assert_eq!(exp_stats, LevelSet::from_str(xml_str).unwrap().stats());
+        
+        let lsr = LevelSet{ name: String::new(), levels: vec![] };
+        assert_eq!(SetStats{ valid_levels: 0, error_levels: 0,
+                min_width: 0, max_width: 0, mean_width: 0.0,
+                min_height: 0, max_height: 0, mean_height: 0.0, total_packs: 0 },
+                lsr.stats());
+    }
+    
+    #[test]
+    fn test_validate() {
+        let lset = LevelSet::from_str(r##"; Microban
+
+; Copyright: David W Skinner
+
+; funny
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+
+; bad
+####
+#.x#
+####
+
+; unsolvable
+#######
+#  $  #
+#@    #
+#   . #
+#######
+
+; nopack
+#####
+#@ .#
+#####
+"##).unwrap();
+        let vs = lset.validate();
+        assert_eq!(4, vs.len());
+        assert_eq!(LevelValidation{ name: "funny".to_string(), parse: Ok(()),
+                check: Some(Ok(())), solvable: Some(Solvability::Solvable) }, vs[0]);
+        assert_eq!(LevelValidation{ name: "bad".to_string(),
+                parse: Err(ParseError::WrongField(2, 1)),
+                check: None, solvable: None }, vs[1]);
+        assert_eq!(LevelValidation{ name: "unsolvable".to_string(), parse: Ok(()),
+                check: Some(Ok(())), solvable: Some(Solvability::Unsolvable) }, vs[2]);
+        assert_eq!("nopack", vs[3].name);
+        assert!(matches!(vs[3].check, Some(Err(_))));
+        assert_eq!(None, vs[3].solvable);
+        // limit of search reached
+        let vs = lset.validate_with_limit(1);
+        assert_eq!(Some(Solvability::LimitReached), vs[0].solvable);
+        assert_eq!(Some(Solvability::Unsolvable), vs[2].solvable);
+    }
+    
+    #[test]
+    fn test_errors() {
+        match LevelSet::from_file("/nonexistent/levelset.sok") {
+            Err(LevelSetError::Io(e)) => assert_eq!(io::ErrorKind::NotFound, e.kind()),
+            r => panic!("Unexpected result {:?}", r),
+        }
+        
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Microban</Bad>
+</SokobanLevels>"##;
+        match LevelSet::from_str(input_str) {
+            Err(LevelSetError::Xml(_)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<Level Id="funny">
+  <L>####</L>
+</Level>"##;
+        match LevelSet::from_str(input_str) {
+            Err(LevelSetError::XmlParse(BadStructure)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Microban</Title>
+  <LevelCollection>
+    <Level Id="funny" Width="x4">
+      <L>####</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        match LevelSet::from_str(input_str) {
+            Err(LevelSetError::ParseInt(_)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+    
+    #[test]
+    fn test_from_path_typed() {
+        // XML without declaration - not detected by content
+        let xml_str = r##"<SokobanLevels>
+  <Title>Microban</Title>
+  <LevelCollection>
+    <Level Id="blocky">
+      <L>######</L>
+      <L>#    #</L>
+      <L># #@ #</L>
+      <L># $* #</L>
+      <L># .* #</L>
+      <L>#    #</L>
+      <L>######</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        let text_str = r##"; Microban
+
+; Copyright: David W Skinner
+
+; blocky
+######
+#    #
+# #@ #
+# $* #
+# .* #
+#    #
+######
+"##;
+        let exp_lsr = LevelSet{ name: "Microban".to_string(),
+            levels: vec![
+                Ok(Level::from_str("blocky", 6, 7,
+                    "######\
+                     #    #\
+                     # #@ #\
+                     # $* #\
+                     # .* #\
+                     #    #\
+                     ######").unwrap()),
+            ] };
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        for (ext, content) in vec![("slc", xml_str), ("XML", xml_str),
+                    ("sok", text_str), ("txt", text_str), ("lev", text_str)] {
+            let path = dir.join(format!("sokoban_test_{}.{}", pid, ext));
+            std::fs::write(&path, content).unwrap();
+            let lsr = LevelSet::from_path_typed(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(exp_lsr, lsr.unwrap());
+        }
+        // XML content with text extension parsed as text - no levels.
+        let path = dir.join(format!("sokoban_test_{}_xml.txt", pid));
+        std::fs::write(&path, xml_str).unwrap();
+        let lsr = LevelSet::from_path_typed(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_ne!(exp_lsr, lsr.unwrap());
+        // JSON
+        let path = dir.join(format!("sokoban_test_{}.json", pid));
+        #[cfg(feature = "serde")]
+        std::fs::write(&path, serde_json::to_string(&exp_lsr).unwrap()).unwrap();
+        #[cfg(not(feature = "serde"))]
+        std::fs::write(&path, "{}").unwrap();
+        let lsr = LevelSet::from_path_typed(&path);
+        std::fs::remove_file(&path).unwrap();
+        #[cfg(feature = "serde")]
+        assert_eq!(exp_lsr, lsr.unwrap());
+        #[cfg(not(feature = "serde"))]
+        match lsr {
+            Err(LevelSetError::UnsupportedFormat(f)) => assert_eq!("json", f),
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let lsr = LevelSet::from_str(r##"; Microban
+
+; funny
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+; Author: David W Skinner
+; @2,3: start here
+
+; bad
+####
+#.x#
+####
+"##).unwrap();
+        assert!(lsr.has_errors());
+        let json = serde_json::to_string(&lsr).unwrap();
+        assert_eq!(lsr, serde_json::from_str::<LevelSet>(&json).unwrap());
+    }
+    
+    #[test]
+    fn test_write_to_xml() {
+        let lsr = LevelSet{ name: "Microban & <others>".to_string(),
+            levels: vec![
+                Ok(Level::from_str("funny", 6, 7,
+                    "####  \
+                     # .#  \
+                     #  ###\
+                     #*@  #\
+                     #  $ #\
+                     #  ###\
+                     ####  ").unwrap()),
+                Err(LevelParseError{ number: 1, name: "bad".to_string(),
+                    error: WrongField(2, 2) }),
+                Ok(Level::from_str("harder", 9, 6,
+                    "  ####   \
+                     ###  ####\
+                     #     $ #\
+                     # #  #$ #\
+                     # . .#@ #\
+                     #########").unwrap()),
+            ] };
+        let exp_lsr = LevelSet{ name: "Microban & <others>".to_string(),
+            levels: vec![
+                Ok(Level::from_str("funny", 6, 7,
+                    "####  \
+                     # .#  \
+                     #  ###\
+                     #*@  #\
+                     #  $ #\
+                     #  ###\
+                     ####  ").unwrap()),
+                Ok(Level::from_str("harder", 9, 6,
+                    "  ####   \
+                     ###  ####\
+                     #     $ #\
+                     # #  #$ #\
+                     # . .#@ #\
+                     #########").unwrap()),
+            ] };
+        
+        let mut bytes = vec![];
+        lsr.write_to_xml_with_policy(&mut bytes, ErrorLevelPolicy::Skip).unwrap();
+        let out = String::from_utf8(bytes).unwrap();
+        assert!(!out.contains("<!--"));
+        assert!(out.contains(r#"<Level Id="harder" Width="9" Height="6">"#));
+        assert!(out.contains("<L>  ####</L>"));
+        assert_eq!(exp_lsr, LevelSet::from_str(&out).unwrap());
+        
+        let mut bytes = vec![];
+        lsr.write_to_xml_with_policy(&mut bytes, ErrorLevelPolicy::Comment).unwrap();
+        let out = String::from_utf8(bytes).unwrap();
+        let comment_pos = out.find("<!-- error: Nr: 1, Name: bad, Error: Wrong field 2x2 -->")
+                .unwrap();
+        assert!(out.find("\"funny\"").unwrap() < comment_pos);
+        assert!(comment_pos < out.find("\"harder\"").unwrap());
+        assert_eq!(exp_lsr, LevelSet::from_str(&out).unwrap());
+        
+        let mut bytes2 = vec![];
+        lsr.write_to_xml(&mut bytes2).unwrap();
+        assert_eq!(out.as_bytes(), bytes2.as_slice());
+        
+        // escaping of identifiers and names of levels
+        let mut level = Level::from_str("a<b>&\"c\"", 5, 3,
+            "#####\
+             #@$.#\
+             #####").unwrap();
+        level.name = "Tom & Jerry".to_string();
+        let lsr = LevelSet{ name: "Cats & dogs".to_string(), levels: vec![Ok(level)] };
+        let mut bytes = vec![];
+        lsr.write_to_xml(&mut bytes).unwrap();
+        let out = String::from_utf8(bytes).unwrap();
+        assert!(out.contains("<Title>Cats &amp; dogs</Title>"));
+        assert!(out.contains(r#"<Level Id="a&lt;b&gt;&amp;&quot;c&quot;" Width="5" Height="3">"#));
+        assert_eq!(lsr, LevelSet::from_str(&out).unwrap());
+    }
+    
+    #[test]
+    fn test_binary() {
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="SokobanLev.xsd">
+  <Title>Microban</Title>
+  <Email>sasquatch@bentonrea.com</Email>
+  <Url>http://users.bentonrea.com/~sasquatch/sokoban/</Url>
+  <LevelCollection Copyright="David W Skinner" MaxWidth="30" MaxHeight="17">
+    <Level Id="funny">
+      <L>####</L>
+      <L># .#</L>
+      <L>#  ###</L>
+      <L>#*@  #</L>
+      <L>#  $ #</L>
+      <L>#  ###</L>
+      <L>####</L>
+    </Level>
+    <Level Id="blocky">
+      <L>######</L>
+      <L>#    #</L>
+      <L># #@ #</L>
+      <L># $* #</L>
+      <L># .* #</L>
+      <L>#    #</L>
+      <L>######</L>
+    </Level>
+    <Level Id="harder">
+      <L>  ####</L>
+      <L>###  ####</L>
+      <L># b   $ #</L>
+      <L># #  #$ #</L>
+      <L># . .#@ #</L>
+      <L>#########</L>
+    </Level>
+    <Level Id="odd">
+      <L>#####</L>
+      <L>#@$.#</L>
+      <L>#####</L>
+    </Level>
+    <Level Id="L1">
+      <Title>First</Title>
+      <L>#####</L>
+      <L>#.$@#</L>
+      <L>#####</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        let lsr = LevelSet::from_str(input_str).unwrap();
+        assert_eq!("L1", lsr.levels()[4].as_ref().unwrap().id());
+        let mut bytes = vec![];
+        lsr.write_binary(&mut bytes).unwrap();
+        assert_eq!(BINARY_MAGIC, &bytes[0..8]);
+        let lsr2 = LevelSet::read_binary(&mut io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(lsr, lsr2);
+        
+        // first version of format - identifier is name of level
+        let mut bytes = BINARY_MAGIC_V1.to_vec();
+        bytes.extend_from_slice(&[1, 0, 0, 0, b'S', 1, 0, 0, 0, 0, 1, 0, 0, 0, b'a',
+                3, 0, 0, 0, 3, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x11, 0x11, 0x13, 0x11, 0x01]);
+        let lsr2 = LevelSet::read_binary(&mut io::Cursor::new(&bytes)).unwrap();
+        assert_eq!("a", lsr2.levels()[0].as_ref().unwrap().id());
+        assert_eq!("a", lsr2.levels()[0].as_ref().unwrap().name());
+        
+        // bad magic
+        bytes[0] = b'X';
+        match LevelSet::read_binary(&mut io::Cursor::new(&bytes)) {
+            Err(LevelSetError::BinaryParse(BadMagic)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        // truncated
+        bytes[0] = b'S';
+        bytes.truncate(bytes.len()-3);
+        assert!(LevelSet::read_binary(&mut io::Cursor::new(&bytes)).is_err());
+        
+        // too long name and too big level
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        match LevelSet::read_binary(&mut io::Cursor::new(&bytes)) {
+            Err(LevelSetError::BinaryParse(BadContent)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        let mut bytes = BINARY_MAGIC.to_vec();
+        // empty levelset name, one level with empty name and identifier
+        bytes.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        match LevelSet::read_binary(&mut io::Cursor::new(&bytes)) {
+            Err(LevelSetError::BinaryParse(BadContent)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        // length of name beyond end of data
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[100, 0, 0, 0, b'a']);
+        match LevelSet::read_binary(&mut io::Cursor::new(&bytes)) {
+            Err(LevelSetError::Io(e)) => assert_eq!(io::ErrorKind::UnexpectedEof, e.kind()),
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+    
+    #[test]
+    fn test_round_trip_level_details() {
+        let lsr = LevelSet::from_str(r##"; Microban
+
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+; funny
+; Author: David W Skinner
+; Copyright & <c>
+; @2,3: start <here>
+; @1,1:
+
+#####
+#@$.#
+#####
+; plain
+"##).unwrap();
+        assert_eq!("Author: David W Skinner\nCopyright & <c>",
+                lsr.levels()[0].as_ref().unwrap().comment());
+        assert_eq!(&vec![(2, 3, "start <here>".to_string()), (1, 1, String::new())],
+                lsr.levels()[0].as_ref().unwrap().annotations());
+        
+        let mut bytes = vec![];
+        lsr.write_binary(&mut bytes).unwrap();
+        assert_eq!(lsr, LevelSet::read_binary(&mut io::Cursor::new(&bytes)).unwrap());
+        
+        let mut bytes = vec![];
+        lsr.write_to_xml(&mut bytes).unwrap();
+        let out = String::from_utf8(bytes).unwrap();
+        assert!(out.contains("<Comment>Author: David W Skinner\nCopyright &amp; &lt;c&gt;\
+                </Comment>"));
+        assert_eq!(1, out.matches("<Comment>").count());
+        assert!(out.contains(r#"<Annotation X="2" Y="3">start &lt;here&gt;</Annotation>"#));
+        assert_eq!(lsr, LevelSet::from_str(&out).unwrap());
+    }
+    
+    #[test]
+    fn test_read_from_xml() {
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="SokobanLev.xsd">
+  <Title>Microban</Title>
+  <Email>sasquatch@bentonrea.com</Email>
+  <Url>http://users.bentonrea.com/~sasquatch/sokoban/</Url>
+  <LevelCollection Copyright="David W Skinner" MaxWidth="30" MaxHeight="17">
+    <Level Id="funny" Width="6" Height="7">
+      <L>####</L>
+      <L># .#</L>
+      <L>#  ###</L>
+      <L>#*@  #</L>
+      <L>#  $ #</L>
+      <L>#  ###</L>
+      <L>####</L>
+    </Level>
+    <Level Id="blocky" Width="6" Height="7">
+      <L>######</L>
+      <L>#    #</L>
+      <L># #@ #</L>
+      <L># $* #</L>
+      <L># .* #</L>
+      <L>#    #</L>
+      <L>######</L>
+    </Level>
+    <Level Id="harder" Width="9" Height="6">
+      <L>  ####</L>
+      <L>###  ####</L>
+      <L>#     $ #</L>
+      <L># #  #$ #</L>
+      <L># . .#@ #</L>
+      <L>#########</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        
+            let lsr = LevelSet::from_str(input_str).unwrap();
+            let exp_lsr = LevelSet{ name: "Microban".to_string(),
+            levels: vec![
+                Ok(Level::from_str("funny", 6, 7,
+                    "####  \
+                     # .#  \
+                     #  ###\
+                     #*@  #\
+                     #  $ #\
+                     #  ###\
+                     ####  ").unwrap()),
+                Ok(Level::from_str("blocky", 6, 7,
+                    "######\
+                     #    #\
+                     # #@ #\
+                     # $* #\
+                     # .* #\
+                     #    #\
+                     ######").unwrap()),
+                Ok(Level::from_str("harder", 9, 6,
+                    "  ####   \
                      ###  ####\
                      #     $ #\
                      # #  #$ #\
@@ -737,4 +2268,201 @@ Microban IV (102 puzzles, August 2010) This set includes a series of alphabet
             ] };
             assert_eq!(exp_lsr, lsr);
     }
+    
+    #[test]
+    fn test_read_from_text_rle() {
+        let input_str = r##"; RLE
+
+Level 1
+2 5#
+3#2 .#
+#@ $2 #
+7#
+; first
+
+5#|#@$.#|5#
+; second
+
+7#
+#@$.3
+7#
+; bad
+"##;
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(LevelSet{ name: "RLE".to_string(), levels: vec![
+            Ok(Level::from_str("first", 7, 4,
+                "  #####\
+                 ###  .#\
+                 #@ $  #\
+                 #######").unwrap()),
+            Ok(Level::from_str("second", 5, 3,
+                "#####\
+                 #@$.#\
+                 #####").unwrap()),
+            Err(LevelParseError{ number: 2, name: "bad".to_string(),
+                    error: WrongField(5, 1) }),
+            ] }, lset);
+    }
+    
+    #[test]
+    fn test_write_to_text() {
+        let input_str = r##"; Small set
+
+ #####
+ #@$.#
+ #####
+; first
+; @3,1: target
+
+######
+#.$  #
+#  $@#
+#.   #
+######
+; second
+"##;
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(2, lset.levels().len());
+        let mut out = vec![];
+        lset.write_to_text(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(input_str, out);
+        assert_eq!(lset, LevelSet::from_str(&out).unwrap());
+        
+        // level with error
+        let lset = LevelSet::from_str("; Bad\n\n#####\n#@x.#\n#####\n; bad\n\n\
+                #####\n#@$.#\n#####\n; good\n").unwrap();
+        let mut out = vec![];
+        lset.write_to_text(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!("; Bad\n\n; error: Nr: 0, Name: bad, Error: Wrong field 2x1\n\n\
+                #####\n#@$.#\n#####\n; good\n", out);
+        let lset2 = LevelSet::from_str(&out).unwrap();
+        assert_eq!(&lset.levels()[1..], &lset2.levels()[..]);
+    }
+    
+    #[test]
+    fn test_read_from_text_comments() {
+        let input_str = r##"; Commented
+
+; Copyright: Nobody
+
+; first
+; Author: John
+; Difficulty: easy
+#####
+#@$.#
+#####
+
+; second
+; @1,1: player
+;
+; Comment: hard one
+#####
+#.$@#
+#####
+"##;
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(LevelSet{ name: "Commented".to_string(), levels: vec![
+            Ok(Level::from_str("first", 5, 3, "#####\
+                 #@$.#\
+                 #####").unwrap().with_comment("Author: John\nDifficulty: easy")),
+            Ok(Level::from_str("second", 5, 3, "#####\
+                 #.$@#\
+                 #####").unwrap().with_annotation(1, 1, "player")
+                 .with_comment("Comment: hard one")),
+            ] }, lset);
+        
+        // comments after level
+        let input_str = r##"; Commented
+
+#####
+#@$.#
+#####
+; first
+; Author: John
+; Difficulty: easy
+
+#####
+#.$@#
+#####
+; second
+"##;
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert_eq!("Author: John\nDifficulty: easy",
+                lset.levels()[0].as_ref().unwrap().comment());
+        assert_eq!("", lset.levels()[1].as_ref().unwrap().comment());
+        let mut out = vec![];
+        lset.write_to_text(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(input_str, out);
+    }
+    
+    #[test]
+    fn test_read_from_xml_without_collection() {
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Flat</Title>
+  <Level Id="first" Width="5" Height="3">
+    <L>#####</L>
+    <L>#@$.#</L>
+    <L>#####</L>
+  </Level>
+  <Level Id="second">
+    <L>#####</L>
+    <L>#.$@#</L>
+    <L>#####</L>
+  </Level>
+</SokobanLevels>"##;
+        let lset = LevelSet::from_str(input_str).unwrap();
+        assert_eq!(LevelSet{ name: "Flat".to_string(), levels: vec![
+                Ok(Level::from_str("first", 5, 3, "#####\
+                                                   #@$.#\
+                                                   #####").unwrap()),
+                Ok(Level::from_str("second", 5, 3, "#####\
+                                                    #.$@#\
+                                                    #####").unwrap())] }, lset);
+    }
+    
+    #[test]
+    fn test_rename_levels_from_titles() {
+        let input_str = r##"<?xml version="1.0" encoding="utf-8"?>
+<SokobanLevels>
+  <Title>Ids</Title>
+  <LevelCollection>
+    <Level Id="x1f" Width="5" Height="3">
+      <L>#####</L>
+      <L>#@$.#</L>
+      <L>#####</L>
+    </Level>
+    <Level Id="x2a" Width="5" Height="3">
+      <Title>Second</Title>
+      <L>#####</L>
+      <L>#.$@#</L>
+      <L>#####</L>
+    </Level>
+    <Level Id="x3c" Width="4">
+      <L>####</L>
+      <L>#@x#</L>
+    </Level>
+  </LevelCollection>
+</SokobanLevels>"##;
+        let mut lset = LevelSet::from_str(input_str).unwrap();
+        let level = lset.levels()[1].as_ref().unwrap();
+        assert_eq!(("Second", "x2a"), (level.name().as_str(), level.id().as_str()));
+        lset.rename_levels_from_titles();
+        let names: Vec<_> = lset.levels().iter().map(|lr| match lr {
+            Ok(l) => (l.name().clone(), l.id().clone()),
+            Err(e) => (e.name.clone(), String::new()),
+        }).collect();
+        assert_eq!(vec![("Level 1".to_string(), "x1f".to_string()),
+                ("Second".to_string(), "x2a".to_string()),
+                ("x3c".to_string(), String::new())], names);
+        
+        // ids and names are kept in XML
+        let mut out = vec![];
+        lset.write_to_xml_with_policy(&mut out, ErrorLevelPolicy::Skip).unwrap();
+        let lset2 = LevelSet::from_str(std::str::from_utf8(&out).unwrap()).unwrap();
+        assert_eq!(&lset.levels()[0..2], &lset2.levels()[..]);
+    }
 }
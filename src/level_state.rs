@@ -17,16 +17,19 @@
 // License along with this library; if not, write to the Free Software
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 
+use std::collections::VecDeque;
+
 use crate::defs::*;
 
-use crate::Level;
+use crate::{Level,Session,SessionEvent};
+use crate::level::{is_locked_block,is_locked_in_corner};
 use Field::*;
 use Direction::*;
 use CheckError::*;
 
 /// LevelState is state game in given a level. A level state contains changed
 /// an area of a level after moves. Initially an area is copied from level.
-#[derive(PartialEq,Eq,Debug,Clone)]
+#[derive(Debug,Clone)]
 pub struct LevelState<'a> {
     pub(crate) level: &'a Level,
     pub(crate) player_x: usize,
@@ -34,17 +37,140 @@ pub struct LevelState<'a> {
     area: Vec<Field>,
     moves: Vec<Direction>,
     pushes_count: usize,
+    checkpoint: Option<Box<Checkpoint>>,
+    // fields changed by last move or undo - not compared by equality.
+    changed_cells: Vec<(usize, usize)>,
+    // frozen packs maintained incrementally - not compared by equality.
+    frozen: FrozenSet,
+    // maximal number of moves in history - None if unlimited.
+    max_history: Option<usize>,
+    // number of oldest moves dropped from history.
+    dropped_moves: usize,
+    // true if session is recorded - not compared by equality.
+    recording: bool,
+    // recorded session - not compared by equality.
+    session: Option<Session>,
+    // undone moves that can be redone (last undone at end) - not compared by equality.
+    redo: Vec<Direction>,
+    // expected number of moves and pushes - not compared by equality.
+    par: Option<(usize, usize)>,
+    // rules of game.
+    rules: Rules,
+    // numbers of packs pushed by pushes in history - not compared by equality.
+    push_chains: Vec<usize>,
+    // identifiers of packs at fields (indexed like area) - not compared by equality.
+    box_ids: Vec<Option<usize>>,
+    // numbers of pushes of packs indexed by identifier - not compared by equality.
+    push_counts: Vec<usize>,
+}
+
+impl<'a> PartialEq for LevelState<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.level == other.level && self.player_x == other.player_x &&
+            self.player_y == other.player_y && self.area == other.area &&
+            self.moves == other.moves && self.pushes_count == other.pushes_count &&
+            self.checkpoint == other.checkpoint && self.max_history == other.max_history &&
+            self.dropped_moves == other.dropped_moves && self.rules == other.rules
+    }
+}
+
+impl<'a> Eq for LevelState<'a> {}
+
+// saved state of level state - used by checkpoint and rollback.
+#[derive(PartialEq,Eq,Debug,Clone)]
+struct Checkpoint {
+    player_x: usize,
+    player_y: usize,
+    area: Vec<Field>,
+    moves: Vec<Direction>,
+    pushes_count: usize,
+    dropped_moves: usize,
+    push_chains: Vec<usize>,
+    box_ids: Vec<Option<usize>>,
+    push_counts: Vec<usize>,
+}
+
+// return true if pack at position is frozen - pack in corner (not on target) or
+// pack in 2x2 block of packs and walls that contains pack not on target.
+fn is_frozen_at(width: usize, height: usize, area: &[Field], pos: usize) -> bool {
+    if !area[pos].is_pack() { return false; }
+    let x = pos % width;
+    let y = pos / width;
+    if x > 0 && x+1 < width && y > 0 && y+1 < height &&
+            is_locked_in_corner(width, area, pos) {
+        return true;
+    }
+    // 2x2 blocks that contains this pack
+    (y.saturating_sub(1)..(y+1).min(height-1)).any(|by|
+        (x.saturating_sub(1)..(x+1).min(width-1))
+            .any(|bx| is_locked_block(width, area, by*width + bx)))
+}
+
+// assign identifiers to packs in order of their positions in area.
+fn new_box_ids(area: &[Field]) -> Vec<Option<usize>> {
+    let mut next_id = 0;
+    area.iter().map(|f| if f.is_pack() {
+        next_id += 1;
+        Some(next_id-1)
+    } else { None }).collect()
+}
+
+// set of frozen packs.
+#[derive(PartialEq,Eq,Debug,Clone)]
+struct FrozenSet {
+    frozen: Vec<bool>,
+    count: usize,
+}
+
+impl FrozenSet {
+    fn new(width: usize, height: usize, area: &[Field]) -> FrozenSet {
+        let frozen: Vec<bool> = (0..area.len())
+                .map(|pos| is_frozen_at(width, height, area, pos)).collect();
+        let count = frozen.iter().filter(|x| **x).count();
+        FrozenSet{ frozen, count }
+    }
+    
+    // update frozen packs around changed fields.
+    fn update(&mut self, width: usize, height: usize, area: &[Field],
+                cells: &[(usize, usize)]) {
+        for (cx, cy) in cells {
+            for y in cy.saturating_sub(1)..=(cy+1).min(height-1) {
+                for x in cx.saturating_sub(1)..=(cx+1).min(width-1) {
+                    let pos = y*width + x;
+                    let new_frozen = is_frozen_at(width, height, area, pos);
+                    if new_frozen != self.frozen[pos] {
+                        if new_frozen { self.count += 1; } else { self.count -= 1; }
+                        self.frozen[pos] = new_frozen;
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<'a> LevelState<'a> {
     /// Create new level state from level.
     pub fn new(level: &'a Level) -> Result<LevelState<'a>, CheckErrors> {
+        Self::new_with_rules(level, Rules::default())
+    }
+    
+    /// Create new level state from level with given rules of game.
+    /// Push chain shorter than 1 is treated as 1.
+    pub fn new_with_rules(level: &'a Level, rules: Rules)
+                    -> Result<LevelState<'a>, CheckErrors> {
+        let rules = Rules{ push_chain: rules.push_chain.max(1) };
         if let Some(pp) = level.area.iter().position(|x| x.is_player()) {
             let player_x = pp % level.width();
             let player_y = pp / level.width();
             level.check()?;
             Ok(LevelState{ level, player_x, player_y, area: level.area().clone(),
-                    moves: vec!(), pushes_count: 0 })
+                    moves: vec!(), pushes_count: 0, checkpoint: None,
+                    changed_cells: vec![],
+                    frozen: FrozenSet::new(level.width(), level.height(), level.area()),
+                    max_history: None, dropped_moves: 0, recording: false, session: None,
+                    redo: vec![], par: level.par(), rules, push_chains: vec![],
+                    box_ids: new_box_ids(level.area()),
+                    push_counts: vec![0; level.area().iter().filter(|x| x.is_pack()).count()] })
         } else {
             let mut errors = CheckErrors::new();
             errors.push(NoPlayer);
@@ -52,6 +178,11 @@ impl<'a> LevelState<'a> {
         }
     }
     
+    /// Return rules of game.
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+    
     // Return level.
     pub fn level(&self) -> &'a Level {
         self.level
@@ -70,136 +201,592 @@ impl<'a> LevelState<'a> {
         &self.area
     }
     
+    /// Return number of steps - all moves of player including pushes
+    /// (also moves dropped from history).
+    pub fn steps(&self) -> usize {
+        self.dropped_moves + self.moves.len()
+    }
+    
+    /// Set maximal number of moves in history. If history exceeds limit then
+    /// oldest moves are dropped and they can not be undone. None - unlimited.
+    pub fn set_max_history(&mut self, max_history: Option<usize>) {
+        self.max_history = max_history;
+        self.trim_history();
+    }
+    
+    /// Get maximal number of moves in history.
+    pub fn max_history(&self) -> Option<usize> {
+        self.max_history
+    }
+    
+    // drop oldest moves if history exceeds limit.
+    fn trim_history(&mut self) {
+        if let Some(max_history) = self.max_history {
+            if self.moves.len() > max_history {
+                let dropped = self.moves.len() - max_history;
+                let dropped_pushes = self.moves[0..dropped].iter()
+                        .filter(|d| d.is_push()).count();
+                self.push_chains.drain(0..dropped_pushes);
+                self.moves.drain(0..dropped);
+                self.dropped_moves += dropped;
+            }
+        }
+    }
+    
+    /// Return number of pushes. Pushes are subset of steps.
     pub fn pushes_count(&self) -> usize {
         self.pushes_count
     }
     
+    /// Set expected number of moves and pushes (par) for this level.
+    pub fn set_par(&mut self, moves: usize, pushes: usize) {
+        self.par = Some((moves, pushes));
+    }
+    
+    /// Get expected number of moves and pushes. By default it is taken from level.
+    pub fn par(&self) -> Option<(usize, usize)> {
+        self.par
+    }
+    
+    /// Return true for moves and pushes if their numbers exceed par, or None if
+    /// par is not set.
+    pub fn over_par(&self) -> Option<(bool, bool)> {
+        self.par.map(|(moves, pushes)| (self.steps() > moves, self.pushes_count > pushes))
+    }
+    
     /// Reset level state to original state - undo all moves.
     pub fn reset(&mut self) {
         if let Some(pp) = self.level.area().iter().position(|x| x.is_player()) {
             self.moves = vec!();
+            self.redo.clear();
+            self.push_chains.clear();
+            self.dropped_moves = 0;
             self.player_x = pp % self.level.width();
             self.player_y = pp / self.level.width();
             self.area.copy_from_slice(self.level.area());
             self.pushes_count = 0;
+            self.reset_box_ids();
+            self.changed_cells.clear();
+            self.update_frozen_fully();
+            self.record_event(SessionEvent::Reset);
         } else {
             panic!("No player!");
         }
     }
     
+    /// Return clone of this level state with current area and player position,
+    /// but without history of moves. Current position becomes origin for moves.
+    pub fn clone_fresh(&self) -> LevelState<'a> {
+        LevelState{ level: self.level, player_x: self.player_x, player_y: self.player_y,
+                area: self.area.clone(), moves: vec!(), pushes_count: 0, checkpoint: None,
+                changed_cells: vec![], frozen: self.frozen.clone(),
+                max_history: self.max_history, dropped_moves: 0, recording: false,
+                session: None, redo: vec![], par: self.par,
+                rules: self.rules, push_chains: vec![], box_ids: new_box_ids(&self.area),
+                push_counts: vec![0; self.total_packs()] }
+    }
+    
+    // assign new identifiers to packs in current area and clear their pushes.
+    fn reset_box_ids(&mut self) {
+        self.box_ids = new_box_ids(&self.area);
+        self.push_counts = vec![0; self.total_packs()];
+    }
+    
+    /// Store current state as checkpoint. Rollback returns to this state.
+    pub fn checkpoint(&mut self) {
+        self.checkpoint = Some(Box::new(Checkpoint{ player_x: self.player_x,
+                player_y: self.player_y, area: self.area.clone(),
+                moves: self.moves.clone(), pushes_count: self.pushes_count,
+                dropped_moves: self.dropped_moves, push_chains: self.push_chains.clone(),
+                box_ids: self.box_ids.clone(), push_counts: self.push_counts.clone() }));
+        self.record_event(SessionEvent::Checkpoint);
+    }
+    
+    /// Return to state stored by last checkpoint. Moves made since checkpoint
+    /// are removed. Return false if no checkpoint.
+    pub fn rollback(&mut self) -> bool {
+        if let Some(cp) = &self.checkpoint {
+            self.player_x = cp.player_x;
+            self.player_y = cp.player_y;
+            self.area.copy_from_slice(&cp.area);
+            self.moves = cp.moves.clone();
+            self.pushes_count = cp.pushes_count;
+            self.dropped_moves = cp.dropped_moves;
+            self.push_chains = cp.push_chains.clone();
+            self.box_ids = cp.box_ids.clone();
+            self.push_counts = cp.push_counts.clone();
+            self.redo.clear();
+            self.changed_cells.clear();
+            self.update_frozen_fully();
+            self.record_event(SessionEvent::Rollback);
+            true
+        } else { false }
+    }
+    
+    /// Create new level from current area of level state. The level contains
+    /// current positions of packs and player.
+    pub fn to_level(&self, name: &str) -> Level {
+        Level{ name: name.to_string(), id: name.to_string(), width: self.level.width(),
+                height: self.level.height(), area: self.area.clone(),
+                annotations: self.level.annotations.clone(),
+                comment: self.level.comment.clone() }
+    }
+    
+    /// Return iterator over rows of current area.
+    pub fn area2d(&self) -> impl Iterator<Item=&[Field]> {
+        self.area.chunks(self.level.width())
+    }
+    
+    /// Return iterator over current fields with their positions (x, y, field) in order
+    /// of area (from top to bottom and from left to right).
+    pub fn cells(&self) -> impl Iterator<Item=(usize, usize, Field)> + '_ {
+        area_cells(&self.area, self.level.width())
+    }
+    
+    /// Return current positions (x, y) of packs (including packs on targets).
+    pub fn boxes(&self) -> Vec<(usize, usize)> {
+        field_positions(&self.area, self.level.width(), Field::is_pack)
+    }
+    
+    /// Return positions (x, y) of targets (including occupied targets).
+    pub fn targets(&self) -> Vec<(usize, usize)> {
+        field_positions(&self.area, self.level.width(), Field::is_target)
+    }
+    
+    /// Return current position (x, y) of player.
+    pub fn player(&self) -> (usize, usize) {
+        (self.player_x, self.player_y)
+    }
+    
+    /// Return mask of fields (indexed like area) that contain pack on target.
+    pub fn boxes_on_target_mask(&self) -> Vec<bool> {
+        self.area.iter().map(|x| *x == PackOnTarget).collect()
+    }
+    
     /// Check whether level is done.
     pub fn is_done(&self) -> bool {
-        let packs_num = self.area.iter().filter(|x| x.is_pack()).count();
-        let targets_num = self.area.iter().filter(|x| x.is_target()).count();
-        let packs_on_targets_num = self.area.iter().filter(
-                    |x| **x == PackOnTarget).count();
-        packs_num == packs_on_targets_num && targets_num == packs_on_targets_num
+        let packs_on_targets_num = self.packs_on_targets();
+        self.total_packs() == packs_on_targets_num && self.remaining() == 0
     }
     
-    /// Make move if possible. Return 2 booleans.
-    /// The first boolean indicates that move has been done.
-    /// The second boolean indicates that move push pack.
-    pub fn make_move(&mut self, dir: Direction) -> (bool, bool) {
+    /// Return number of packs on targets.
+    pub fn packs_on_targets(&self) -> usize {
+        self.area.iter().filter(|x| **x == PackOnTarget).count()
+    }
+    
+    /// Return number of all packs (including packs on targets).
+    pub fn total_packs(&self) -> usize {
+        self.area.iter().filter(|x| x.is_pack()).count()
+    }
+    
+    /// Return number of targets not covered by pack.
+    pub fn remaining(&self) -> usize {
+        self.area.iter().filter(|x| **x == Target || **x == PlayerOnTarget).count()
+    }
+    
+    /// Check whether level is lost - some pack is locked and can not be moved
+    /// to target (pack in corner or 2x2 block of packs and walls).
+    /// Same as `is_deadlocked`.
+    pub fn is_lost(&self) -> bool {
+        self.is_deadlocked()
+    }
+    
+    /// Return fields reachable by player from its current position.
+    /// Packs and walls are blockers.
+    pub fn reachable(&self) -> Vec<bool> {
+        self.reachable_from(self.player_x, self.player_y)
+    }
+    
+    /// Return canonical position of player - the top-left-most field of region
+    /// reachable by player. States that differ only by position of player
+    /// in this region have same canonical position.
+    pub fn normalized_player(&self) -> (usize, usize) {
+        let width = self.level.width();
+        let pos = self.reachable().iter().position(|x| *x)
+                .unwrap_or(self.player_y*width + self.player_x);
+        (pos % width, pos / width)
+    }
+    
+    /// Return fields reachable from given position. Packs and walls are blockers.
+    /// Return no reachable fields if position is out of area or is blocked.
+    pub fn reachable_from(&self, x: usize, y: usize) -> Vec<bool> {
         let width = self.level.width();
         let height = self.level.height();
-        let this_pos = self.player_y*width + self.player_x;
-        // get some setup for direction. next positions, new player position and directions.
-        let (pnext_pos, pnext2_pos, new_x, new_y, dir, push_dir) = match dir {
-            Left|PushLeft => {
-                let pnext_pos = if self.player_x>0
-                    { Some(this_pos-1) } else { None };
-                let pnext2_pos = if self.player_x>1
-                    { Some(this_pos-2) } else { None };
-                (pnext_pos, pnext2_pos,
-                self.player_x-1, self.player_y, Left, PushLeft)
+        let is_free = |pos: usize| self.area[pos] != Wall && !self.area[pos].is_pack();
+        let mut filled = vec![false; width*height];
+        if x >= width || y >= height || !is_free(y*width + x) {
+            return filled;
+        }
+        let mut stk = vec![y*width + x];
+        filled[y*width + x] = true;
+        while let Some(pos) = stk.pop() {
+            let (px, py) = (pos % width, pos / width);
+            let neighbors = [
+                if px > 0 { Some(pos-1) } else { None },
+                if px+1 < width { Some(pos+1) } else { None },
+                if py > 0 { Some(pos-width) } else { None },
+                if py+1 < height { Some(pos+width) } else { None }];
+            for npos in neighbors.iter().flatten() {
+                if !filled[*npos] && is_free(*npos) {
+                    filled[*npos] = true;
+                    stk.push(*npos);
+                }
             }
-            Right|PushRight => {
-                let pnext_pos = if self.player_x<width-1
-                    { Some(this_pos+1) } else { None };
-                let pnext2_pos = if self.player_x<width-2
-                    { Some(this_pos+2) } else { None };
-                (pnext_pos, pnext2_pos,
-                self.player_x+1, self.player_y, Right, PushRight)
+        }
+        filled
+    }
+    
+    /// Walk player to given position by shortest path without pushing packs.
+    /// Return false if position is not reachable.
+    pub fn walk_to(&mut self, x: usize, y: usize) -> bool {
+        let width = self.level.width();
+        let height = self.level.height();
+        if x >= width || y >= height {
+            return false;
+        }
+        let start = self.player_y*width + self.player_x;
+        let target = y*width + x;
+        let is_free = |pos: usize| self.area[pos] != Wall && !self.area[pos].is_pack();
+        // direction of move that reached field
+        let mut from: Vec<Option<Direction>> = vec![None; width*height];
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(pos) = queue.pop_front() {
+            if pos == target { break; }
+            let (px, py) = (pos % width, pos / width);
+            let neighbors = [
+                (px > 0, Left, pos.wrapping_sub(1)),
+                (px+1 < width, Right, pos+1),
+                (py > 0, Up, pos.wrapping_sub(width)),
+                (py+1 < height, Down, pos+width)];
+            for (valid, dir, npos) in neighbors.iter() {
+                if *valid && *npos != start && from[*npos].is_none() && is_free(*npos) {
+                    from[*npos] = Some(*dir);
+                    queue.push_back(*npos);
+                }
             }
-            Up|PushUp => {
-                let pnext_pos = if self.player_y>0
-                    { Some(this_pos-width) } else { None };
-                let pnext2_pos = if self.player_y>1
-                    { Some(this_pos-2*width) } else { None };
-                (pnext_pos, pnext2_pos,
-                self.player_x, self.player_y-1, Up, PushUp)
+        }
+        if target != start && from[target].is_none() {
+            return false;
+        }
+        // reconstruct path from target to start
+        let mut path = vec![];
+        let mut pos = target;
+        while let Some(dir) = from[pos] {
+            path.push(dir);
+            pos = match dir {
+                Left => pos+1,
+                Right => pos-1,
+                Up => pos+width,
+                _ => pos-width,
+            };
+        }
+        path.iter().rev().for_each(|dir| { self.make_move(*dir); });
+        true
+    }
+    
+    /// Return true if pack at given position can be pushed in direction now:
+    /// the field behind pack must be reachable by player and field ahead must be free.
+    pub fn can_push_box(&self, box_x: usize, box_y: usize, dir: Direction) -> bool {
+        self.can_push_box_with(&self.reachable(), box_x, box_y, dir)
+    }
+    
+    // check whether pack can be pushed with precomputed fields reachable by player.
+    fn can_push_box_with(&self, reachable: &[bool], box_x: usize, box_y: usize,
+                dir: Direction) -> bool {
+        let width = self.level.width();
+        if box_x >= width || box_y >= self.level.height() ||
+                !self.area[box_y*width + box_x].is_pack() {
+            return false;
+        }
+        let pos = box_y*width + box_x;
+        let dir = match dir {
+            Left|PushLeft => Left,
+            Right|PushRight => Right,
+            Up|PushUp => Up,
+            Down|PushDown => Down,
+            _ => return false,
+        };
+        // packs ahead are pushed too if rules allow it
+        match self.pos_in_dir(pos, dir.opposite(), 1) {
+            Some(behind) => reachable[behind] && self.push_chain_len(pos, dir).is_ok(),
+            None => false,
+        }
+    }
+    
+    /// Return packs that player can get behind with directions in that they can be
+    /// pushed now. Packs are ordered by position in area.
+    pub fn actionable_boxes(&self) -> Vec<((usize, usize), Vec<Direction>)> {
+        let width = self.level.width();
+        let height = self.level.height();
+        let reachable = self.reachable();
+        let touched = |x: usize, y: usize| (x > 0 && reachable[y*width + x-1]) ||
+                (x+1 < width && reachable[y*width + x+1]) ||
+                (y > 0 && reachable[(y-1)*width + x]) ||
+                (y+1 < height && reachable[(y+1)*width + x]);
+        self.area.iter().enumerate().filter(|(_, f)| f.is_pack())
+            .map(|(pos, _)| (pos % width, pos / width))
+            .filter(|&(x, y)| touched(x, y))
+            .map(|(x, y)| ((x, y), Direction::all_moves()
+                    .filter(|d| self.can_push_box_with(&reachable, x, y, *d))
+                    .collect::<Vec<_>>()))
+            .filter(|(_, dirs)| !dirs.is_empty()).collect()
+    }
+    
+    // update frozen packs around changed fields.
+    fn update_frozen(&mut self) {
+        self.frozen.update(self.level.width(), self.level.height(), &self.area,
+                &self.changed_cells);
+    }
+    
+    // recompute all frozen packs.
+    fn update_frozen_fully(&mut self) {
+        self.frozen = FrozenSet::new(self.level.width(), self.level.height(), &self.area);
+    }
+    
+    /// Check whether level is deadlocked - some pack is frozen (locked in corner or
+    /// in 2x2 block of packs and walls). Frozen packs are maintained incrementally.
+    pub fn is_deadlocked(&self) -> bool {
+        self.frozen.count != 0
+    }
+    
+    /// Return legal moves (base directions) that do not freeze any pack. Packs
+    /// frozen before move are ignored, so other moves are still returned.
+    pub fn legal_nondeadlocking_moves(&self) -> Vec<Direction> {
+        Direction::all_moves().filter(|d| {
+            let mut lstate = self.clone_fresh();
+            lstate.try_move(*d).is_ok() && !lstate.frozen.frozen.iter()
+                    .zip(&self.frozen.frozen).any(|(new, old)| *new && !*old)
+        }).collect()
+    }
+    
+    /// Check whether move is possible without making it. Return direction of move
+    /// that would be done (push direction if move pushes pack) or reason why
+    /// move is blocked. Pull direction and no direction are unsupported - use
+    /// `pull_move` to pull.
+    pub fn peek_move(&self, dir: Direction) -> Result<Direction, BlockedReason> {
+        let (dir, push_dir) = match dir {
+            Left|PushLeft => (Left, PushLeft),
+            Right|PushRight => (Right, PushRight),
+            Up|PushUp => (Up, PushUp),
+            Down|PushDown => (Down, PushDown),
+            _ => { return Err(BlockedReason::Unsupported); }
+        };
+        let this_pos = self.player_y*self.level.width() + self.player_x;
+        let next_pos = self.pos_in_dir(this_pos, dir, 1).ok_or(BlockedReason::Edge)?;
+        // check whether if wall
+        match self.area[next_pos] {
+            Wall => Err(BlockedReason::Wall),
+            Pack|PackOnTarget => self.push_chain_len(next_pos, dir).map(|_| push_dir),
+            _ => Ok(dir)
+        }
+    }
+    
+    // return position moved n fields in direction or None if it is beyond edge.
+    fn pos_in_dir(&self, pos: usize, dir: Direction, n: usize) -> Option<usize> {
+        let width = self.level.width();
+        let (x, y) = (pos % width, pos / width);
+        match dir {
+            Left|PushLeft|PullLeft => x.checked_sub(n).map(|x| y*width + x),
+            Right|PushRight|PullRight => if x+n < width { Some(pos+n) } else { None },
+            Up|PushUp|PullUp => y.checked_sub(n).map(|y| y*width + x),
+            Down|PushDown|PullDown => if y+n < self.level.height()
+                    { Some(pos+n*width) } else { None },
+            NoDirection => None,
+        }
+    }
+    
+    // return number of packs in a row from pack at position that would be pushed
+    // in direction or reason why they can not be pushed.
+    fn push_chain_len(&self, pos: usize, dir: Direction) -> Result<usize, BlockedReason> {
+        let mut len = 1;
+        loop {
+            let after = self.pos_in_dir(pos, dir, len).ok_or(BlockedReason::BoxAgainstWall)?;
+            if self.area[after] == Wall {
+                return Err(BlockedReason::BoxAgainstWall);
+            } else if !self.area[after].is_pack() {
+                return Ok(len);
+            } else if len == self.rules.push_chain {
+                return Err(BlockedReason::BoxAgainstBox);
             }
-            Down|PushDown => {
-                let pnext_pos = if self.player_y<height-1
-                    { Some(this_pos+width) } else { None };
-                let pnext2_pos = if self.player_y<height-2
-                    { Some(this_pos+2*width) }else { None };
-                (pnext_pos, pnext2_pos,
-                self.player_x, self.player_y+1, Down, PushDown)
+            len += 1;
+        }
+    }
+    
+    /// Make move if possible. Return direction of done move (push direction
+    /// if move pushes pack) or reason why move is blocked.
+    pub fn try_move(&mut self, dir: Direction) -> Result<Direction, BlockedReason> {
+        let dir = self.peek_move(dir)?;
+        let width = self.level.width();
+        let this_pos = self.player_y*width + self.player_x;
+        let next_pos = self.pos_in_dir(this_pos, dir, 1).unwrap();
+        self.changed_cells.clear();
+        if dir.is_push() {
+            // first pack of chain moves to end of chain
+            let chain = self.push_chain_len(next_pos, dir).unwrap();
+            let end_pos = self.pos_in_dir(next_pos, dir, chain).unwrap();
+            self.area[end_pos].set_pack();
+            // but every pack of chain is pushed by one field
+            for i in (0..chain).rev() {
+                let pos = self.pos_in_dir(next_pos, dir, i).unwrap();
+                let id = self.box_ids[pos].take();
+                if let Some(id) = id {
+                    self.push_counts[id] += 1;
+                }
+                let npos = self.pos_in_dir(pos, dir, 1).unwrap();
+                self.box_ids[npos] = id;
             }
-            NoDirection => (None, None, 0, 0, NoDirection, NoDirection),
-        };
-        
-        if let Some(next_pos) = pnext_pos {
-            // check whether if wall
-            match self.area[next_pos] {
-                Empty|Target => {
-                    self.area[next_pos].set_player();
-                    self.area[this_pos].unset_player();
-                    self.player_x = new_x;
-                    self.player_y = new_y;
-                    self.moves.push(dir);
-                    (true, false)
+            self.pushes_count += 1;
+            self.push_chains.push(chain);
+            self.changed_cells.push((end_pos % width, end_pos / width));
+        }
+        self.area[next_pos].set_player();
+        self.area[this_pos].unset_player();
+        self.player_x = next_pos % width;
+        self.player_y = next_pos / width;
+        self.moves.push(dir);
+        self.redo.clear();
+        self.trim_history();
+        self.record_event(SessionEvent::Move(dir));
+        self.changed_cells.push((this_pos % width, this_pos / width));
+        self.changed_cells.push((self.player_x, self.player_y));
+        self.update_frozen();
+        Ok(dir)
+    }
+    
+    /// Make step - move if possible. Return outcome of move, events produced by move
+    /// and whether level is solved. This is recommended way to make move.
+    pub fn step(&mut self, dir: Direction) -> StepResult {
+        let was_done = self.is_done();
+        let outcome = self.try_move(dir);
+        let mut events = vec![];
+        if let Ok(done_dir) = outcome {
+            events.push(StateEvent::Moved(done_dir));
+            if done_dir.is_push() {
+                let width = self.level.width();
+                // player stands on old position of pack, pack is at end of chain
+                let from = (self.player_x, self.player_y);
+                let chain = self.push_chains.last().copied().unwrap_or(1);
+                let to_pos = self.pos_in_dir(from.1*width + from.0, done_dir, chain).unwrap();
+                let to = (to_pos % width, to_pos / width);
+                events.push(StateEvent::PackPushed{ from, to });
+                let from_target = self.area[from.1*width + from.0].is_target();
+                let to_target = self.area[to.1*width + to.0].is_target();
+                if from_target && !to_target {
+                    events.push(StateEvent::PackOffTarget(from.0, from.1));
                 }
-                Pack|PackOnTarget => {
-                    if let Some(next2_pos) = pnext2_pos {
-                        if self.area[next2_pos] != Wall &&
-                            !self.area[next2_pos].is_pack() {
-                            self.area[next2_pos].set_pack();
-                            self.area[next_pos].set_player();
-                            self.area[this_pos].unset_player();
-                            self.player_x = new_x;
-                            self.player_y = new_y;
-                            self.moves.push(push_dir);
-                            self.pushes_count += 1;
-                            (true, true)
-                        } else { (false, false) }
-                    } else {
-                        (false, false)
-                    }
+                if to_target && !from_target {
+                    events.push(StateEvent::PackOnTarget(to.0, to.1));
                 }
-                _ => (false, false)
             }
-        } else { (false, false) }
+        }
+        let solved = self.is_done();
+        // only move that solves level
+        if solved && !was_done {
+            events.push(StateEvent::Solved);
+        }
+        StepResult{ outcome, events, solved }
+    }
+    
+    /// Make move if possible. Return 2 booleans.
+    /// The first boolean indicates that move has been done.
+    /// The second boolean indicates that move push pack.
+    pub fn make_move(&mut self, dir: Direction) -> (bool, bool) {
+        match self.try_move(dir) {
+            Ok(dir) => (true, dir.is_push()),
+            Err(_) => (false, false),
+        }
+    }
+    
+    /// Make move in direction and pull pack that lies directly behind player
+    /// (at opposite side) to old position of player. If no pack behind player
+    /// then make simple move. Direction can be base, push or pull direction.
+    /// Return true in first field if move has been done and true in second field
+    /// if pack has been pulled. Pulls are not counted as pushes.
+    pub fn pull_move(&mut self, dir: Direction) -> (bool, bool) {
+        let dir = dir.as_move();
+        let width = self.level.width();
+        let this_pos = self.player_y*width + self.player_x;
+        let next_pos = match self.pos_in_dir(this_pos, dir, 1) {
+            Some(next_pos) if self.area[next_pos] != Wall && !self.area[next_pos].is_pack()
+                => next_pos,
+            _ => return (false, false),
+        };
+        let pack_pos = match self.pos_in_dir(this_pos, dir.opposite(), 1) {
+            Some(pack_pos) if self.area[pack_pos].is_pack() => pack_pos,
+            _ => return (self.try_move(dir).is_ok(), false),
+        };
+        let pull_dir = match dir {
+            Left => PullLeft,
+            Right => PullRight,
+            Up => PullUp,
+            _ => PullDown,
+        };
+        self.area[this_pos].unset_player();
+        self.area[this_pos].set_pack();
+        self.area[pack_pos].unset_pack();
+        self.box_ids[this_pos] = self.box_ids[pack_pos].take();
+        self.area[next_pos].set_player();
+        self.player_x = next_pos % width;
+        self.player_y = next_pos / width;
+        self.moves.push(pull_dir);
+        self.redo.clear();
+        self.trim_history();
+        self.record_event(SessionEvent::Move(pull_dir));
+        self.changed_cells.clear();
+        self.changed_cells.push((pack_pos % width, pack_pos / width));
+        self.changed_cells.push((this_pos % width, this_pos / width));
+        self.changed_cells.push((self.player_x, self.player_y));
+        self.update_frozen();
+        (true, true)
+    }
+    
+    /// Make move only if predicate holds for resulting state. Return true
+    /// if move has been done. Predicate gets scratch copy of state after move
+    /// (like `clone_fresh` - without history of moves), so rejected move
+    /// doesn't change this state.
+    pub fn make_move_if(&mut self, dir: Direction, pred: impl Fn(&LevelState) -> bool)
+                    -> bool {
+        let mut scratch = self.clone_fresh();
+        scratch.max_history = None;
+        if scratch.try_move(dir).is_err() || !pred(&scratch) {
+            return false;
+        }
+        self.try_move(dir).is_ok()
     }
     
     /// Undo move. Return true if move undone.
     pub fn undo_move(&mut self) -> bool {
+        self.undo_move_dir().is_some()
+    }
+    
+    /// Undo move. Return direction of undone move or None if no move to undo.
+    pub fn undo_move_dir(&mut self) -> Option<Direction> {
         if let Some(dir) = self.moves.pop() {
             let width = self.level.width();
             let height = self.level.height();
             let this_pos = self.player_y*width + self.player_x;
             
             let (prev_pos, pnext_pos, old_x, old_y) = match dir {
-                Right|PushRight => {
+                Right|PushRight|PullRight => {
                     if self.player_x==0 { panic!("Unexpected frame"); }
                     let next_pos = if dir == PushRight
                         { Some(this_pos+1) } else { None };
                     (this_pos-1, next_pos, self.player_x-1, self.player_y)
                 }
-                Left|PushLeft => {
+                Left|PushLeft|PullLeft => {
                     if self.player_x>=width-1 { panic!("Unexpected frame"); }
                     let next_pos = if dir == PushLeft
                         { Some(this_pos-1) } else { None };
                     (this_pos+1, next_pos, self.player_x+1, self.player_y)
                 }
-                Down|PushDown => {
+                Down|PushDown|PullDown => {
                     if self.player_y==0 { panic!("Unexpected frame"); }
                     let next_pos = if dir == PushDown
                         { Some(this_pos+width) } else { None };
                     (this_pos-width, next_pos, self.player_x, self.player_y-1)
                 }
-                Up|PushUp => {
+                Up|PushUp|PullUp => {
                     if self.player_y>=height-1 { panic!("Unexpected frame"); }
                     let next_pos = if dir == PushUp
                         { Some(this_pos-width) } else { None };
@@ -210,30 +797,262 @@ impl<'a> LevelState<'a> {
                 }
             };
             
-            if let Some(next_pos) = pnext_pos {
+            self.changed_cells.clear();
+            if pnext_pos.is_some() {
+                // pack at end of chain returns to position of player
+                let chain = self.push_chains.pop().unwrap_or(1);
+                let next_pos = self.pos_in_dir(this_pos, dir, chain).unwrap();
                 self.area[next_pos].unset_pack();
                 self.area[this_pos].set_pack();
+                for i in 0..chain {
+                    let pos = self.pos_in_dir(this_pos, dir, i).unwrap();
+                    let npos = self.pos_in_dir(pos, dir, 1).unwrap();
+                    let id = self.box_ids[npos].take();
+                    if let Some(id) = id {
+                        self.push_counts[id] -= 1;
+                    }
+                    self.box_ids[pos] = id;
+                }
                 self.pushes_count -= 1;
+                self.changed_cells.push((next_pos % width, next_pos / width));
+            } else if dir.is_pull() {
+                // pulled pack returns to field behind old position of player
+                let pack_pos = self.pos_in_dir(prev_pos, dir.opposite(), 1).unwrap();
+                self.area[prev_pos].unset_pack();
+                self.area[pack_pos].set_pack();
+                self.box_ids[pack_pos] = self.box_ids[prev_pos].take();
+                self.area[this_pos].unset_player();
+                self.changed_cells.push((pack_pos % width, pack_pos / width));
             } else {
                 self.area[this_pos].unset_player();
             }
             self.area[prev_pos].set_player();
+            self.changed_cells.push((self.player_x, self.player_y));
+            self.changed_cells.push((old_x, old_y));
             self.player_x = old_x;
             self.player_y = old_y;
-            true
+            self.update_frozen();
+            self.record_event(SessionEvent::Undo);
+            self.redo.push(dir);
+            Some(dir)
+        } else { None }
+    }
+    
+    /// Redo last undone move. Return true if move redone.
+    pub fn redo_move(&mut self) -> bool {
+        if let Some(dir) = self.redo.pop() {
+            // keep rest of redo stack - move clears it
+            let redo = std::mem::take(&mut self.redo);
+            let done = if dir.is_pull() { self.pull_move(dir).1 }
+                    else { self.try_move(dir).is_ok() };
+            self.redo = redo;
+            done
         } else { false }
     }
     
-    /// Get all moves.
+    /// Return true if some undone move can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    
+    /// Return number of pushes of box with given index. Boxes are indexed in order
+    /// of their positions (row by row) in area at creation or reset of level state
+    /// and every box keeps its index while it is moved. Pulls are not counted.
+    /// Return 0 if no box with that index.
+    pub fn pushes_for_box(&self, box_index: usize) -> usize {
+        self.push_counts.get(box_index).copied().unwrap_or(0)
+    }
+    
+    /// Replay moves given in LURD format on level. Return iterator that yields
+    /// area after every move or index of move if move is illegal (then iteration ends).
+    pub fn replay<'b>(level: &'b Level, lurd: &'b str)
+                -> impl Iterator<Item=Result<Vec<Field>, usize>> + 'b {
+        let mut lstate = LevelState::new(level).ok();
+        let mut failed = false;
+        lurd.chars().enumerate().map_while(move |(i, c)| {
+            if failed { return None; }
+            if let (Some(lstate), Some(dir)) = (&mut lstate, Direction::from_lurd(c)) {
+                if lstate.peek_move(dir) == Ok(dir) {
+                    lstate.make_move(dir);
+                    return Some(Ok(lstate.area.clone()));
+                }
+            }
+            failed = true;
+            Some(Err(i))
+        })
+    }
+    
+    /// Apply moves until first failure. Move fails if it can not be done or if done
+    /// move differs from given (push direction must push pack, base direction
+    /// must not push). Return number of done moves.
+    pub fn apply_moves(&mut self, moves: &[Direction]) -> usize {
+        moves.iter().take_while(|dir| {
+            if self.peek_move(**dir) == Ok(**dir) {
+                self.make_move(**dir);
+                true
+            } else { false }
+        }).count()
+    }
+    
+    /// Apply moves in LURD format (lowercase - move, uppercase - push) until first
+    /// failure or wrong character. Return number of done moves.
+    pub fn apply_lurd(&mut self, lurd: &str) -> usize {
+        let moves: Vec<Direction> = lurd.chars().map_while(Direction::from_lurd).collect();
+        self.apply_moves(&moves)
+    }
+    
+    /// Return moves in LURD format (lowercase - move, uppercase - push) or None if
+    /// some moves have been dropped from history or moves contain pulls (then moves
+    /// can not be replayed from start of level).
+    pub fn moves_as_lurd(&self) -> Option<String> {
+        if self.dropped_moves != 0 {
+            return None;
+        }
+        // moves never contain NoDirection - such move is always blocked,
+        // pulls have no LURD character
+        self.moves.iter().map(|d| d.to_lurd()).collect()
+    }
+    
+    /// Enable or disable recording of session. Enabling starts new session
+    /// from current state. Disabling keeps recorded session. Session contains
+    /// all events even if moves are dropped from history.
+    pub fn record(&mut self, enabled: bool) {
+        if enabled {
+            self.session = Some(Session{ width: self.level.width(),
+                    height: self.level.height(), area: self.area.clone(), events: vec![] });
+        }
+        self.recording = enabled;
+    }
+    
+    // record event if recording is enabled.
+    fn record_event(&mut self, event: SessionEvent) {
+        if self.recording {
+            if let Some(session) = &mut self.session {
+                session.events.push(event);
+            }
+        }
+    }
+    
+    /// Return recorded session or None if no session has been recorded.
+    pub fn export_session(&self) -> Option<Session> {
+        self.session.clone()
+    }
+    
+    /// Return net moves of play (without undone moves) - solution if level is done.
+    /// Moves dropped from history are not included.
+    pub fn record_session(&self) -> Vec<Direction> {
+        self.moves.clone()
+    }
+    
+    /// Replay moves on level. Base direction can push pack, push direction must push
+    /// pack, pull direction must pull pack. Return final level state or index of first illegal move.
+    pub fn replay_moves(level: &'a Level, moves: &[Direction])
+                -> Result<LevelState<'a>, usize> {
+        let mut lstate = LevelState::new(level).map_err(|_| 0usize)?;
+        for (i, dir) in moves.iter().enumerate() {
+            match lstate.peek_move(*dir) {
+                _ if dir.is_pull() => if !lstate.pull_move(*dir).1 { return Err(i); },
+                Ok(done) if done == *dir || !dir.is_push() => { lstate.make_move(*dir); }
+                _ => return Err(i),
+            }
+        }
+        Ok(lstate)
+    }
+    
+    /// Play session on level. Return final level state or index of event that
+    /// can not be played. Board of session must have dimensions and number
+    /// of packs of level.
+    pub fn play_session(level: &'a Level, session: &Session) -> Result<LevelState<'a>, usize> {
+        let mut lstate = LevelState::new(level).map_err(|_| 0usize)?;
+        if session.width != level.width() || session.height != level.height() ||
+                session.area.len() != level.area().len() ||
+                session.area.iter().filter(|x| x.is_player()).count() != 1 ||
+                session.area.iter().filter(|x| x.is_pack()).count() != lstate.total_packs() {
+            return Err(0);
+        }
+        let pp = session.area.iter().position(|x| x.is_player()).ok_or(0usize)?;
+        lstate.area = session.area.clone();
+        lstate.player_x = pp % session.width;
+        lstate.player_y = pp / session.width;
+        lstate.reset_box_ids();
+        lstate.update_frozen_fully();
+        for (i, event) in session.events.iter().enumerate() {
+            match event {
+                SessionEvent::Move(dir) if dir.is_pull() => {
+                    if !lstate.pull_move(*dir).1 { return Err(i); }
+                }
+                SessionEvent::Move(dir) => {
+                    if lstate.peek_move(*dir) != Ok(*dir) { return Err(i); }
+                    lstate.make_move(*dir);
+                }
+                SessionEvent::Undo => if !lstate.undo_move() { return Err(i); },
+                SessionEvent::Reset => lstate.reset(),
+                SessionEvent::Checkpoint => lstate.checkpoint(),
+                SessionEvent::Rollback => if !lstate.rollback() { return Err(i); },
+            }
+        }
+        Ok(lstate)
+    }
+    
+    /// Get fields changed by last move or undo. Reset and rollback clear them.
+    pub fn changed_cells(&self) -> &Vec<(usize, usize)> {
+        &self.changed_cells
+    }
+    
+    /// Get all moves in history (without moves dropped from history).
     pub fn moves(&self) -> &Vec<Direction> {
         &self.moves
     }
+    
+    /// Get moves made since n-th step (counted from start of level like `steps`).
+    /// Return None if some of these moves have been dropped from history.
+    /// Return empty slice if n is out of range.
+    pub fn moves_since(&self, n: usize) -> Option<&[Direction]> {
+        if n < self.dropped_moves {
+            None
+        } else if n - self.dropped_moves < self.moves.len() {
+            Some(&self.moves[n - self.dropped_moves..])
+        } else { Some(&[]) }
+    }
+    
+    /// Check invariants of level state: area must have exactly one player and
+    /// player position must point to player cell. Available in debug builds,
+    /// tests and with `test-util` feature.
+    #[cfg(any(test, debug_assertions, feature = "test-util"))]
+    pub fn validate_invariants(&self) -> Result<(), String> {
+        let players_num = self.area.iter().filter(|x| x.is_player()).count();
+        if players_num != 1 {
+            return Err(format!("Wrong number of players: {}", players_num));
+        }
+        let width = self.level.width();
+        if self.player_x >= width || self.player_y >= self.level.height() {
+            return Err(format!("Player position {}x{} out of area",
+                    self.player_x, self.player_y));
+        }
+        if !self.area[self.player_y*width + self.player_x].is_player() {
+            return Err(format!("No player at position {}x{}",
+                    self.player_x, self.player_y));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     
+    // expected level state - other fields have default values.
+    fn expected_state<'a>(level: &'a Level, (player_x, player_y): (usize, usize),
+                area: Vec<Field>, moves: Vec<Direction>, pushes_count: usize)
+                -> LevelState<'a> {
+        let frozen = FrozenSet::new(level.width(), level.height(), &area);
+        LevelState{ level, player_x, player_y, area, moves, pushes_count, checkpoint: None,
+            changed_cells: vec![], frozen, max_history: None,
+            dropped_moves: 0, recording: false, session: None, redo: vec![], par: None,
+            rules: Rules::default(), push_chains: vec![], box_ids: vec![],
+            push_counts: vec![] }
+    }
+    
     #[test]
     fn test_failed_new_state() {
         let level = Level::from_str("git", 8, 6,
@@ -272,16 +1091,15 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 1, player_y: 2,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (1, 2),
+            Level::from_str("git", 8, 6,
             " ###### \
              #      #\
              #@  ...#\
              #   $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Left], pushes_count: 0 },
+            vec![Left], 0),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -289,16 +1107,15 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 3, player_y: 2,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (3, 2),
+            Level::from_str("git", 8, 6,
             " ###### \
              #      #\
              #  @...#\
              #   $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Right], pushes_count: 0 },
+            vec![Right], 0),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -306,16 +1123,15 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Up));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 2, player_y: 1,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (2, 1),
+            Level::from_str("git", 8, 6,
             " ###### \
              # @    #\
              #   ...#\
              #   $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Up], pushes_count: 0 },
+            vec![Up], 0),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -323,16 +1139,15 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Down));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 2, player_y: 3,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (2, 3),
+            Level::from_str("git", 8, 6,
             " ###### \
              #      #\
              #   ...#\
              # @ $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Down], pushes_count: 0 },
+            vec![Down], 0),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -349,16 +1164,15 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 1, player_y: 2,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (1, 2),
+            Level::from_str("git", 8, 6,
             " ###### \
              #      #\
              #@.  ..#\
              #   $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Left], pushes_count: 0 },
+            vec![Left], 0),
             lstate);
         let mut lstate2 = lstate.clone();
         assert_eq!(true, lstate2.undo_move());
@@ -366,16 +1180,15 @@ mod test {
         // move to target
         let old_lstate = lstate.clone();
         assert_eq!((true, false), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 2, player_y: 2,
-            area: Level::from_str("git", 8, 6,
+        assert_eq!(expected_state(&level, (2, 2),
+            Level::from_str("git", 8, 6,
             " ###### \
              #      #\
              # +  ..#\
              #   $$$#\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![Left,Right], pushes_count: 0 },
+            vec![Left,Right], 0),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -390,10 +1203,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 1, player_y: 2,
-            area: level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (1, 2), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 6,
@@ -405,10 +1215,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 6, player_y: 2,
-            area: level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (6, 2), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 6,
@@ -420,10 +1227,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Up));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 3, player_y: 1,
-            area: level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (3, 1), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 6,
@@ -435,10 +1239,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Down));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 3, player_y: 4,
-            area: level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (3, 4), level.area().clone(), vec![], 0),
             lstate);
         
         // pushes
@@ -453,9 +1254,8 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 3, player_y: 3,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (3, 3),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
              # ..$  #\
@@ -463,7 +1263,7 @@ mod test {
              #   $  #\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushLeft], pushes_count: 1 },
+            vec![PushLeft], 1),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -471,9 +1271,8 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 5, player_y: 3,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (5, 3),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
              # ..$  #\
@@ -481,7 +1280,7 @@ mod test {
              #   $  #\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushRight], pushes_count: 1 },
+            vec![PushRight], 1),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -489,9 +1288,8 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Up));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 2,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (4, 2),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..$  #\
              # ..@  #\
@@ -499,7 +1297,7 @@ mod test {
              #   $  #\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushUp], pushes_count: 1 },
+            vec![PushUp], 1),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -507,9 +1305,8 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Down));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 4,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (4, 4),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
              # ..$  #\
@@ -517,7 +1314,7 @@ mod test {
              #   @  #\
              #   $  # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushDown], pushes_count: 1 },
+            vec![PushDown], 1),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -534,9 +1331,8 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 3, player_y: 3,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (3, 3),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
              #  .$  #\
@@ -544,7 +1340,7 @@ mod test {
              #   $  #\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushLeft], pushes_count: 1 },
+            vec![PushLeft], 1),
             lstate);
         let mut lstate2 = lstate.clone();
         assert_eq!(true, lstate2.undo_move());
@@ -552,9 +1348,8 @@ mod test {
         
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 2, player_y: 3,
-            area: Level::from_str("git", 8, 7,
+        assert_eq!(expected_state(&level, (2, 3),
+            Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
              #  .$  #\
@@ -562,7 +1357,7 @@ mod test {
              #   $  #\
              #      # \
               ###### ").unwrap().area().clone(),
-            moves: vec![PushLeft, PushLeft], pushes_count: 2 },
+            vec![PushLeft, PushLeft], 2),
             lstate);
         assert_eq!(true, lstate.undo_move());
         assert_eq!(old_lstate, lstate);
@@ -578,10 +1373,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         let level = Level::from_str("git", 8, 7,
             " ###### \
@@ -593,10 +1385,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         let level = Level::from_str("git", 8, 7,
             " ###### \
@@ -608,10 +1397,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Left));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 7,
@@ -624,10 +1410,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         let level = Level::from_str("git", 8, 7,
             " ###### \
@@ -639,10 +1422,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Right));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 7,
@@ -655,10 +1435,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Up));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         let level = Level::from_str("git", 8, 7,
             " ###### \
@@ -670,10 +1447,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Up));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         
         let level = Level::from_str("git", 8, 7,
@@ -686,10 +1460,7 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Down));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
         let level = Level::from_str("git", 8, 7,
             " ###### \
@@ -701,15 +1472,12 @@ mod test {
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
         assert_eq!((false, false), lstate.make_move(Down));
-        assert_eq!(LevelState{ level: &level,
-            player_x: 4, player_y: 3,
-            area:level.area().clone(),
-            moves: vec![], pushes_count: 0 },
+        assert_eq!(expected_state(&level, (4, 3), level.area().clone(), vec![], 0),
             lstate);
     }
     
     #[test]
-    fn test_reset() {
+    fn test_undo_move_dir() {
         let level = Level::from_str("git", 8, 7,
             " ###### \
              # ..   #\
@@ -721,13 +1489,76 @@ mod test {
         let mut lstate = LevelState::new(&level).unwrap();
         let old_lstate = lstate.clone();
         assert_eq!((true, true), lstate.make_move(Left));
-        assert_eq!((true, true), lstate.make_move(Left));
-        lstate.reset();
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        assert_eq!((true, false), lstate.make_move(Up));
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        assert_eq!((true, false), lstate.make_move(Left));
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        assert_eq!(Some(Left), lstate.undo_move_dir());
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        assert_eq!(Some(Up), lstate.undo_move_dir());
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        assert_eq!(Some(PushLeft), lstate.undo_move_dir());
+        assert_eq!(Ok(()), lstate.validate_invariants());
         assert_eq!(old_lstate, lstate);
+        assert_eq!(None, lstate.undo_move_dir());
     }
     
     #[test]
-    fn test_is_done() {
+    fn test_peek_move_and_try_move() {
+        let level = Level::from_str("git", 8, 7,
+            " ###### \
+             # ..   #\
+             #...$  #\
+             # $$@$##\
+             #   $  #\
+             #   #  # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let old_lstate = lstate.clone();
+        assert_eq!(Err(BlockedReason::BoxAgainstBox), lstate.peek_move(Left));
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Right));
+        assert_eq!(Ok(PushUp), lstate.peek_move(Up));
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Down));
+        assert_eq!(Err(BlockedReason::Unsupported), lstate.peek_move(NoDirection));
+        assert_eq!(Err(BlockedReason::Unsupported), lstate.peek_move(PullUp));
+        assert_eq!(Err(BlockedReason::Unsupported), lstate.try_move(PullLeft));
+        assert_eq!(Err(BlockedReason::BoxAgainstBox), lstate.try_move(Left));
+        assert_eq!(old_lstate, lstate);
+        assert_eq!(Ok(PushUp), lstate.try_move(Up));
+        assert_eq!(Ok(Right), lstate.peek_move(Right));
+        assert_eq!(Ok(Right), lstate.peek_move(PushRight));
+        assert_eq!(Ok(Left), lstate.peek_move(Left));
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Up));
+        assert_eq!(Ok(Right), lstate.try_move(Right));
+        assert_eq!(Ok(PushDown), lstate.peek_move(Down));
+        assert_eq!(Ok(Right), lstate.try_move(Right));
+        assert_eq!(Err(BlockedReason::Wall), lstate.peek_move(Right));
+        assert_eq!(Err(BlockedReason::Wall), lstate.try_move(Down));
+        assert_eq!(&vec![PushUp, Right, Right], lstate.moves());
+        assert_eq!(1, lstate.pushes_count());
+        
+        // moves at edge of area
+        let level = Level::from_str("git", 4, 3,
+            "@$  \
+             $   \
+             #.$ ").unwrap();
+        let lstate = expected_state(&level, (0, 0), level.area().clone(), vec![], 0);
+        assert_eq!(Err(BlockedReason::Edge), lstate.peek_move(Left));
+        assert_eq!(Err(BlockedReason::Edge), lstate.peek_move(Up));
+        assert_eq!(Ok(PushRight), lstate.peek_move(Right));
+        let lstate = expected_state(&level, (3, 2), level.area().clone(), vec![], 0);
+        assert_eq!(Err(BlockedReason::Edge), lstate.peek_move(Right));
+        assert_eq!(Err(BlockedReason::Edge), lstate.peek_move(Down));
+        assert_eq!(Ok(PushLeft), lstate.peek_move(Left));
+        let lstate = expected_state(&level, (1, 1), level.area().clone(), vec![], 0);
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Left));
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Up));
+        assert_eq!(Ok(Down), lstate.peek_move(Down));
+    }
+    
+    #[test]
+    fn test_validate_invariants() {
         let level = Level::from_str("git", 8, 6,
             " ###### \
              #      #\
@@ -736,11 +1567,1048 @@ mod test {
              #      # \
               ###### ").unwrap();
         let mut lstate = LevelState::new(&level).unwrap();
-        for m in vec![Down, Down, Right, Right, Right,
-                    Up, Down,Right, Up, Down, Right, Up] {
-            assert_eq!(false, lstate.is_done());
-            lstate.make_move(m);
-        }
-        assert_eq!(true, lstate.is_done());
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        lstate.make_move(Right);
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        // corrupt player position
+        let mut bad_lstate = lstate.clone();
+        bad_lstate.player_x = 1;
+        assert_eq!(Err("No player at position 1x2".to_string()),
+                bad_lstate.validate_invariants());
+        bad_lstate.player_x = 8;
+        assert_eq!(Err("Player position 8x2 out of area".to_string()),
+                bad_lstate.validate_invariants());
+        // corrupt area
+        let mut bad_lstate = lstate.clone();
+        bad_lstate.area[8*1 + 1] = Player;
+        assert_eq!(Err("Wrong number of players: 2".to_string()),
+                bad_lstate.validate_invariants());
+    }
+    
+    #[test]
+    fn test_pushes_for_box() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(0, lstate.pushes_for_box(0));
+        // push first box up and down repeatedly
+        for m in vec![Down, Down, Right, Right, Right, Up, Left, Up, Up, Right, Down,
+                    Left, Down, Down, Right, Up] {
+            assert!(lstate.make_move(m).0);
+        }
+        assert_eq!(3, lstate.pushes_count());
+        assert_eq!(3, lstate.pushes_for_box(0));
+        assert_eq!(0, lstate.pushes_for_box(1));
+        assert_eq!(0, lstate.pushes_for_box(2));
+        assert_eq!(0, lstate.pushes_for_box(3));
+        lstate.undo_move();
+        assert_eq!(2, lstate.pushes_for_box(0));
+        // push second box
+        for m in vec![Right, Up] {
+            assert!(lstate.make_move(m).0);
+        }
+        assert_eq!(2, lstate.pushes_for_box(0));
+        assert_eq!(1, lstate.pushes_for_box(1));
+        assert_eq!(0, lstate.pushes_for_box(2));
+        // pushes are kept after dropping moves from history
+        lstate.set_max_history(Some(1));
+        assert_eq!(2, lstate.pushes_for_box(0));
+        assert_eq!(1, lstate.pushes_for_box(1));
+        assert!(lstate.undo_move());
+        assert_eq!(0, lstate.pushes_for_box(1));
+        lstate.reset();
+        assert_eq!(0, lstate.pushes_for_box(0));
+    }
+    
+    #[test]
+    fn test_legal_nondeadlocking_moves() {
+        let level = Level::from_str("git", 6, 5,
+            "######\
+             # $@ #\
+             #    #\
+             #   .#\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(!lstate.is_lost());
+        assert_eq!(vec![Right, Down], lstate.legal_nondeadlocking_moves());
+        // push to corner - lost
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert!(lstate.is_lost());
+        
+        // pack already frozen doesn't exclude other moves
+        let level = Level::from_str("git", 7, 5,
+            "#######\
+             # $@  #\
+             #    $#\
+             # ..  #\
+             #######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert!(lstate.is_lost());
+        assert_eq!(vec![Right, Down], lstate.legal_nondeadlocking_moves());
+        for _ in 0..3 {
+            assert_eq!((true, false), lstate.make_move(Right));
+        }
+        // push down freezes next pack
+        assert_eq!(Ok(PushDown), lstate.peek_move(Down));
+        assert_eq!(vec![Left], lstate.legal_nondeadlocking_moves());
+    }
+    
+    #[test]
+    fn test_reachable_from() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #   $  #\
+             #@ #...#\
+             #  #$$ #\
+             #  #   # \
+              ###### ").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        let reachable = lstate.reachable();
+        assert_eq!(reachable, lstate.reachable_from(1, 2));
+        let exp_reachable: Vec<bool> = " ###### \
+             #xxx#  #\
+             #xx#   #\
+             #xx#   #\
+             #xx#   # \
+              ###### ".chars().map(|c| c == 'x').collect();
+        assert_eq!(exp_reachable, reachable);
+        // behind pack
+        let reachable = lstate.reachable_from(5, 1);
+        assert!(reachable[8*4 + 4]);
+        assert!(!reachable[8*1 + 1]);
+        // wall, pack and out of range
+        assert!(lstate.reachable_from(0, 1).iter().all(|x| !x));
+        assert!(lstate.reachable_from(4, 1).iter().all(|x| !x));
+        assert!(lstate.reachable_from(8, 1).iter().all(|x| !x));
+        assert!(lstate.reachable_from(1, 6).iter().all(|x| !x));
+    }
+    
+    #[test]
+    fn test_normalized_player() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #   $  #\
+             #@ #...#\
+             #  #$$ #\
+             #  #   # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!((1, 1), lstate.normalized_player());
+        for m in vec![Down, Right, Down, Up, Up, Up, Right] {
+            assert_eq!((true, false), lstate.make_move(m));
+            assert_eq!((1, 1), lstate.normalized_player());
+        }
+        // push opens region behind pack - anchor stays the same
+        assert_eq!((true, true), lstate.make_move(Right));
+        assert_eq!((1, 1), lstate.normalized_player());
+        assert_eq!((true, false), lstate.make_move(Down));
+        assert_eq!((true, false), lstate.make_move(Right));
+        assert_eq!((1, 1), lstate.normalized_player());
+    }
+    
+    #[test]
+    fn test_max_history() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(None, lstate.max_history());
+        lstate.set_max_history(Some(3));
+        let mut states = vec![];
+        for m in vec![Down, Down, Right, Right, Right, Up] {
+            states.push(lstate.area().clone());
+            assert!(lstate.make_move(m).0);
+        }
+        assert_eq!(&vec![Right, Right, PushUp], lstate.moves());
+        assert_eq!((6, 1), (lstate.steps(), lstate.pushes_count()));
+        // dropped moves can not be returned
+        assert_eq!(None, lstate.moves_as_lurd());
+        assert_eq!(None, lstate.moves_since(2));
+        assert_eq!(Some(&[Right, PushUp][..]), lstate.moves_since(4));
+        for i in (3..6).rev() {
+            assert!(lstate.undo_move());
+            assert_eq!(&states[i], lstate.area());
+        }
+        assert!(!lstate.undo_move());
+        assert_eq!((3, 0), (lstate.steps(), lstate.pushes_count()));
+        assert_eq!((2, 4), (lstate.player_x(), lstate.player_y()));
+        assert_eq!(Ok(()), lstate.validate_invariants());
+        lstate.reset();
+        assert_eq!(0, lstate.steps());
+        assert_eq!(Some(String::new()), lstate.moves_as_lurd());
+        assert_eq!(Some(3), lstate.max_history());
+    }
+    
+    #[test]
+    fn test_session() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(None, lstate.export_session());
+        lstate.make_move(Right);
+        lstate.record(true);
+        for m in vec![Down, Down, Right, Right, Up] {
+            lstate.make_move(m);
+        }
+        lstate.undo_move();
+        lstate.undo_move();
+        for m in vec![Right, Up, Left] {
+            lstate.make_move(m);
+        }
+        lstate.record(false);
+        lstate.make_move(Down);
+        let session = lstate.export_session().unwrap();
+        assert_eq!(10, session.events.len());
+        assert_eq!(SessionEvent::Move(PushUp), session.events[4]);
+        lstate.undo_move();
+        
+        let mut bytes = vec![];
+        session.write_sokrec(&mut bytes).unwrap();
+        assert_eq!(" ###### \n#      #\n# @ ...#\n#   $$$#\n#      #\n ###### \n\
+                    ddrrU--rUl\n", std::str::from_utf8(&bytes[4..]).unwrap());
+        let session2 = Session::read_sokrec(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(session, session2);
+        let played = LevelState::play_session(&level, &session2).unwrap();
+        assert_eq!(lstate.area(), played.area());
+        assert_eq!((lstate.player_x(), lstate.player_y()),
+                (played.player_x(), played.player_y()));
+        assert_eq!(lstate.pushes_count(), played.pushes_count());
+        assert_eq!(lstate.moves()[1..], played.moves()[..]);
+        
+        // bad event
+        let mut bad_session = session.clone();
+        bad_session.events.insert(0, SessionEvent::Undo);
+        assert_eq!(Err(0), LevelState::play_session(&level, &bad_session).map(|_| ()));
+        assert!(Session::read_sokrec(&mut std::io::Cursor::new(b"8 6\n ###### \n")).is_err());
+        // malformed board
+        let mut bad_session = session.clone();
+        bad_session.area.truncate(20);
+        assert_eq!(Err(0), LevelState::play_session(&level, &bad_session).map(|_| ()));
+        let mut bad_session = session.clone();
+        bad_session.area[8*4 + 1] = Pack;
+        assert_eq!(Err(0), LevelState::play_session(&level, &bad_session).map(|_| ()));
+        let mut bad_session = session.clone();
+        bad_session.area[8*4 + 1] = Player;
+        assert_eq!(Err(0), LevelState::play_session(&level, &bad_session).map(|_| ()));
+        // too big board
+        for header in [&b"4294967296 4294967296\n"[..], &b"100000 100000\n"[..]] {
+            let err = Session::read_sokrec(&mut std::io::Cursor::new(header)).unwrap_err();
+            assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+        }
+        
+        // checkpoint and rollback
+        let mut lstate = LevelState::new(&level).unwrap();
+        lstate.record(true);
+        for m in vec![Down, Down, Right, Right, Right, Up] {
+            lstate.make_move(m);
+        }
+        lstate.checkpoint();
+        for m in vec![Down, Right, Up] {
+            lstate.make_move(m);
+        }
+        assert!(lstate.rollback());
+        lstate.make_move(Left);
+        let session = lstate.export_session().unwrap();
+        assert_eq!(Some(&SessionEvent::Checkpoint), session.events.get(6));
+        assert_eq!(Some(&SessionEvent::Rollback), session.events.get(10));
+        let mut bytes = vec![];
+        session.write_sokrec(&mut bytes).unwrap();
+        assert!(std::str::from_utf8(&bytes).unwrap().ends_with("ddrrrU=drU<l\n"));
+        let session2 = Session::read_sokrec(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(session, session2);
+        let played = LevelState::play_session(&level, &session2).unwrap();
+        assert_eq!(lstate.area(), played.area());
+        assert_eq!(lstate.moves(), played.moves());
+        // rollback without checkpoint
+        let mut bad_session = session.clone();
+        bad_session.events.remove(6);
+        assert_eq!(Err(9), LevelState::play_session(&level, &bad_session).map(|_| ()));
+    }
+    
+    #[test]
+    fn test_steps() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!((0, 0), (lstate.steps(), lstate.pushes_count()));
+        for m in vec![Down, Down, Right, Right, Right, Up, Down, Right, Up] {
+            assert!(lstate.make_move(m).0);
+            assert_eq!(lstate.moves().len(), lstate.steps());
+            assert!(lstate.pushes_count() <= lstate.steps());
+        }
+        assert_eq!((9, 2), (lstate.steps(), lstate.pushes_count()));
+        lstate.undo_move();
+        assert_eq!((8, 1), (lstate.steps(), lstate.pushes_count()));
+    }
+    
+    #[test]
+    fn test_changed_cells() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(lstate.changed_cells().is_empty());
+        assert_eq!((true, false), lstate.make_move(Down));
+        assert_eq!(&vec![(1, 2), (1, 3)], lstate.changed_cells());
+        for m in vec![Down, Right, Right, Right] {
+            lstate.make_move(m);
+        }
+        assert_eq!((true, true), lstate.make_move(Up));
+        assert_eq!(&vec![(4, 2), (4, 4), (4, 3)], lstate.changed_cells());
+        // failed move doesn't change cells
+        assert_eq!((false, false), lstate.make_move(Right));
+        assert_eq!(&vec![(4, 2), (4, 4), (4, 3)], lstate.changed_cells());
+        assert!(lstate.undo_move());
+        assert_eq!(&vec![(4, 2), (4, 3), (4, 4)], lstate.changed_cells());
+        assert!(lstate.undo_move());
+        assert_eq!(&vec![(4, 4), (3, 4)], lstate.changed_cells());
+        lstate.reset();
+        assert!(lstate.changed_cells().is_empty());
+    }
+    
+    #[test]
+    fn test_is_deadlocked_ignores_packs_on_targets() {
+        let level = Level::from_str("corner", 6, 5,
+            "######\
+             #@$ .#\
+             #  $ #\
+             #   .#\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(!lstate.is_deadlocked());
+        // push pack onto target in corner
+        assert!(lstate.make_move(Right).0);
+        assert!(lstate.make_move(Right).0);
+        assert_eq!(PackOnTarget, lstate.area()[6 + 4]);
+        assert!(!lstate.is_deadlocked());
+        // push other pack to pack on target - 2x2 block of walls and packs
+        for m in vec![Left, Down, Down, Right, Up] {
+            assert!(lstate.make_move(m).0);
+        }
+        assert!(lstate.is_deadlocked());
+        assert!(lstate.is_lost());
+        assert!(lstate.undo_move());
+        assert!(!lstate.is_deadlocked());
+    }
+    
+    #[test]
+    fn test_incremental_frozen() {
+        let level = Level::from_str("git", 8, 7,
+            " ###### \
+             # ..   #\
+             #  .$  #\
+             # .$@$ #\
+             #   $  #\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(!lstate.is_deadlocked());
+        // simple linear congruential generator
+        let mut seed: u32 = 12345;
+        let mut was_deadlocked = false;
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (seed >> 16) % 6;
+            if r < 4 {
+                lstate.make_move(Direction::MOVES[r as usize]);
+            } else {
+                lstate.undo_move();
+            }
+            let full = FrozenSet::new(level.width(), level.height(), lstate.area());
+            assert_eq!(full, lstate.frozen);
+            // level check finds the same locks
+            let locked = lstate.to_level("git").check().err().is_some_and(|errors|
+                errors.into_iter().any(|e| matches!(e, Locked2x2Block(_, _) |
+                        LockedPackApartWalls(_, _))));
+            assert_eq!(locked, lstate.is_deadlocked());
+            was_deadlocked |= lstate.is_deadlocked();
+        }
+        assert!(was_deadlocked);
+        lstate.reset();
+        assert!(!lstate.is_deadlocked());
+    }
+    
+    #[test]
+    fn test_replay() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let frames: Vec<_> = LevelState::replay(&level, "ddrrrUdrUdrU").collect();
+        assert_eq!(12, frames.len());
+        assert!(frames.iter().all(|f| f.is_ok()));
+        let exp_area = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #   ***#\
+             #     @#\
+             #      # \
+              ###### ").unwrap().area().clone();
+        assert_eq!(Some(&Ok(exp_area)), frames.last());
+        
+        // illegal moves: wall and push letter for plain move
+        let frames: Vec<_> = LevelState::replay(&level, "ullr").collect();
+        assert_eq!(2, frames.len());
+        assert!(frames[0].is_ok());
+        assert_eq!(Err(1), frames[1]);
+        let frames: Vec<_> = LevelState::replay(&level, "dRr").collect();
+        assert_eq!(2, frames.len());
+        assert_eq!(Err(1), frames[1]);
+        let frames: Vec<_> = LevelState::replay(&level, "dxr").collect();
+        assert_eq!(Err(1), frames[1]);
+    }
+    
+    #[test]
+    fn test_area2d() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        assert_eq!(6, lstate.area2d().count());
+        assert!(lstate.area2d().all(|row| row.len() == 8));
+        assert_eq!(&[Wall, Player, Empty, Empty, Target, Target, Target, Wall],
+                lstate.area2d().nth(2).unwrap());
+    }
+    
+    #[test]
+    fn test_boxes_on_target_mask() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(lstate.boxes_on_target_mask().iter().all(|x| !x));
+        for m in vec![Down, Down, Right, Right, Right] {
+            lstate.make_move(m);
+        }
+        assert_eq!((true, true), lstate.make_move(Up));
+        let mut exp_mask = vec![false; 8*6];
+        exp_mask[8*2 + 4] = true;
+        assert_eq!(exp_mask, lstate.boxes_on_target_mask());
+    }
+    
+    #[test]
+    fn test_make_move_if() {
+        let level = Level::from_str("git", 6, 5,
+            "######\
+             # $@ #\
+             #    #\
+             #   .#\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let old_lstate = lstate.clone();
+        // fatal push is blocked
+        assert!(!lstate.make_move_if(Left, |s| !s.is_deadlocked()));
+        assert_eq!(old_lstate, lstate);
+        assert!(lstate.changed_cells().is_empty());
+        // illegal move
+        assert!(!lstate.make_move_if(Up, |_| true));
+        assert_eq!(old_lstate, lstate);
+        assert!(lstate.make_move_if(Down, |s| !s.is_deadlocked()));
+        assert_eq!(&vec![Down], lstate.moves());
+        assert!(lstate.make_move_if(Left, |s| !s.is_deadlocked()));
+        assert_eq!(&vec![Down, Left], lstate.moves());
+        
+        // rejected move doesn't drop history and isn't recorded
+        let mut lstate = LevelState::new(&level).unwrap();
+        lstate.set_max_history(Some(1));
+        lstate.record(true);
+        assert_eq!((true, false), lstate.make_move(Right));
+        assert!(lstate.undo_move());
+        assert_eq!((true, false), lstate.make_move(Down));
+        let old_lstate = lstate.clone();
+        let old_changed_cells = lstate.changed_cells().clone();
+        assert!(!lstate.make_move_if(Left, |_| false));
+        assert_eq!(old_lstate, lstate);
+        assert_eq!(&vec![Down], lstate.moves());
+        assert_eq!(1, lstate.steps());
+        assert_eq!(Some("d".to_string()), lstate.moves_as_lurd());
+        assert_eq!(&old_changed_cells, lstate.changed_cells());
+        assert_eq!(vec![SessionEvent::Move(Right), SessionEvent::Undo,
+                SessionEvent::Move(Down)], lstate.export_session().unwrap().events);
+        assert!(lstate.undo_move());
+        assert!(lstate.can_redo());
+        assert!(!lstate.make_move_if(Left, |_| false));
+        assert!(lstate.can_redo());
+    }
+    
+    #[test]
+    fn test_can_push_box() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #   $  #\
+             #@ #...#\
+             #  #$$ #\
+             #  #   # \
+              ###### ").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        assert!(lstate.can_push_box(4, 1, Right));
+        assert!(lstate.can_push_box(4, 1, PushRight));
+        // pushing side not reachable
+        assert!(!lstate.can_push_box(4, 1, Left));
+        assert!(!lstate.can_push_box(5, 3, Down));
+        // pushing side walled off
+        assert!(!lstate.can_push_box(4, 3, Right));
+        // field ahead is not free
+        assert!(!lstate.can_push_box(4, 1, Up));
+        assert!(!lstate.can_push_box(4, 3, Left));
+        // no pack
+        assert!(!lstate.can_push_box(3, 1, Right));
+        assert!(!lstate.can_push_box(4, 1, NoDirection));
+    }
+    
+    #[test]
+    fn test_over_par() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(None, lstate.par());
+        assert_eq!(None, lstate.over_par());
+        lstate.set_par(5, 0);
+        for m in vec![Down, Down, Right, Right, Right] {
+            assert!(lstate.make_move(m).0);
+            assert_eq!(Some((false, false)), lstate.over_par());
+        }
+        assert_eq!((true, true), lstate.make_move(Up));
+        assert_eq!(Some((true, true)), lstate.over_par());
+        assert!(lstate.undo_move());
+        assert_eq!(Some((false, false)), lstate.over_par());
+        assert!(lstate.make_move(Left).0);
+        assert_eq!(Some((true, false)), lstate.over_par());
+        
+        // par from level comment
+        let level = level.with_comment("Author: Nobody\nmoves=97 pushes=42");
+        let lstate = LevelState::new(&level).unwrap();
+        assert_eq!(Some((97, 42)), lstate.par());
+        assert_eq!(Some((false, false)), lstate.over_par());
+    }
+    
+    #[test]
+    fn test_redo_move() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(!lstate.can_redo());
+        assert!(!lstate.redo_move());
+        for m in vec![Down, Down, Right, Right, Right, Up] {
+            assert!(lstate.make_move(m).0);
+        }
+        // box on (4,2) on target
+        assert_eq!(PackOnTarget, lstate.area()[2*8 + 4]);
+        let orig = lstate.clone();
+        assert!(lstate.undo_move());
+        assert!(lstate.undo_move());
+        assert!(lstate.can_redo());
+        assert_eq!(Pack, lstate.area()[3*8 + 4]);
+        assert_eq!(Target, lstate.area()[2*8 + 4]);
+        assert!(lstate.redo_move());
+        assert!(lstate.can_redo());
+        assert!(lstate.redo_move());
+        assert!(!lstate.can_redo());
+        assert!(!lstate.redo_move());
+        assert_eq!(orig, lstate);
+        assert_eq!(PackOnTarget, lstate.area()[2*8 + 4]);
+        assert_eq!(vec![(4, 2), (4, 4), (4, 3)], lstate.changed_cells().to_vec());
+        
+        // new move clears redo stack
+        assert!(lstate.undo_move());
+        assert!(lstate.make_move(Left).0);
+        assert!(!lstate.can_redo());
+        // make_move_if keeps redo stack if move is not done
+        assert!(lstate.undo_move());
+        assert!(!lstate.make_move_if(Right, |_| false));
+        assert!(lstate.can_redo());
+        // reset clears redo stack
+        lstate.reset();
+        assert!(!lstate.can_redo());
+    }
+    
+    #[test]
+    fn test_step() {
+        let level = Level::from_str("git", 7, 3,
+            "#######\
+             #@ $.*#\
+             #######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(StepResult{ outcome: Ok(Right), events: vec![StateEvent::Moved(Right)],
+                solved: false }, lstate.step(Right));
+        assert_eq!(StepResult{ outcome: Err(BlockedReason::Wall), events: vec![],
+                solved: false }, lstate.step(Up));
+        assert_eq!(StepResult{ outcome: Ok(PushRight), events: vec![
+                StateEvent::Moved(PushRight),
+                StateEvent::PackPushed{ from: (3, 1), to: (4, 1) },
+                StateEvent::PackOnTarget(4, 1), StateEvent::Solved],
+                solved: true }, lstate.step(Right));
+        assert_eq!(StepResult{ outcome: Err(BlockedReason::BoxAgainstBox), events: vec![],
+                solved: true }, lstate.step(Right));
+        // further moves don't solve level again
+        assert_eq!(StepResult{ outcome: Ok(Left), events: vec![StateEvent::Moved(Left)],
+                solved: true }, lstate.step(Left));
+    }
+    
+    #[test]
+    fn test_walk_to() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #   $  #\
+             #@ #...#\
+             #  #$$ #\
+             #  #   # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert!(lstate.walk_to(1, 2));
+        assert!(lstate.moves().is_empty());
+        assert!(lstate.walk_to(3, 1));
+        assert_eq!(&vec![Right, Up, Right], lstate.moves());
+        assert_eq!((3, 1), (lstate.player_x(), lstate.player_y()));
+        assert!(lstate.walk_to(2, 4));
+        assert_eq!(&vec![Right, Up, Right, Left, Down, Down, Down], lstate.moves());
+        assert_eq!(0, lstate.pushes_count());
+        // behind pack, wall, pack and outside area
+        assert!(!lstate.walk_to(5, 1));
+        assert!(!lstate.walk_to(3, 2));
+        assert!(!lstate.walk_to(4, 1));
+        assert!(!lstate.walk_to(8, 1));
+        assert_eq!(7, lstate.moves().len());
+    }
+    
+    #[test]
+    fn test_actionable_boxes() {
+        let level = Level::from_str("git", 7, 6,
+            "#######\
+             #     #\
+             # @$  #\
+             #    .#\
+             #     #\
+             #######").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        assert_eq!(vec![((3, 2), vec![Left, Right, Up, Down])], lstate.actionable_boxes());
+        
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #   $  #\
+             #@ #...#\
+             #  #$$ #\
+             #  #   # \
+              ###### ").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        assert_eq!(vec![((4, 1), vec![Right])], lstate.actionable_boxes());
+    }
+    
+    #[test]
+    fn test_checkpoint_and_rollback() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(false, lstate.rollback());
+        for m in vec![Down, Down, Right, Right, Right, Up] {
+            lstate.make_move(m);
+        }
+        lstate.checkpoint();
+        let cp_lstate = lstate.clone();
+        for m in vec![Down, Right, Up, Down, Right] {
+            lstate.make_move(m);
+            assert_eq!(Ok(()), lstate.validate_invariants());
+        }
+        assert_ne!(cp_lstate.area(), lstate.area());
+        assert_eq!(true, lstate.rollback());
+        assert_eq!(cp_lstate, lstate);
+        assert_eq!(6, lstate.moves().len());
+        assert_eq!(1, lstate.pushes_count());
+        // rollback after undoing moves before checkpoint
+        lstate.undo_move();
+        lstate.undo_move();
+        assert_eq!(true, lstate.rollback());
+        assert_eq!(cp_lstate, lstate);
+        // rollback after reset
+        lstate.reset();
+        assert_eq!(true, lstate.rollback());
+        assert_eq!(cp_lstate, lstate);
+    }
+    
+    #[test]
+    fn test_to_level() {
+        let level = Level::from_str("git", 8, 7,
+            " ###### \
+             # ..   #\
+             #  .$  #\
+             # .$@$ #\
+             #   $  #\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        for m in vec![Left, Left, Right, Up, Right, Up] {
+            assert!(lstate.make_move(m).0);
+        }
+        assert_eq!(3, lstate.pushes_count());
+        let level2 = lstate.to_level("git2");
+        assert_eq!("git2", level2.name());
+        assert_eq!((8, 7), (level2.width(), level2.height()));
+        let lstate2 = LevelState::new(&level2).unwrap();
+        assert_eq!(lstate.area(), lstate2.area());
+        assert_eq!((lstate.player_x(), lstate.player_y()),
+                (lstate2.player_x(), lstate2.player_y()));
+        assert!(lstate2.moves().is_empty());
+    }
+    
+    #[test]
+    fn test_reset() {
+        let level = Level::from_str("git", 8, 7,
+            " ###### \
+             # ..   #\
+             #  .$  #\
+             # .$@$ #\
+             #   $  #\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let old_lstate = lstate.clone();
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert_eq!((true, true), lstate.make_move(Left));
+        lstate.reset();
+        assert_eq!(old_lstate, lstate);
+    }
+    
+    #[test]
+    fn test_clone_fresh() {
+        let level = Level::from_str("git", 8, 7,
+            " ###### \
+             # ..   #\
+             #  .$  #\
+             # .$@$ #\
+             #   $  #\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert_eq!((true, false), lstate.make_move(Up));
+        let mut lstate2 = lstate.clone_fresh();
+        assert_eq!(lstate.area(), lstate2.area());
+        assert_eq!((lstate.player_x(), lstate.player_y()),
+                (lstate2.player_x(), lstate2.player_y()));
+        assert!(lstate2.moves().is_empty());
+        assert_eq!(0, lstate2.pushes_count());
+        assert_eq!(false, lstate2.undo_move());
+        assert_eq!(lstate.area(), lstate2.area());
+        // original state still have moves
+        assert_eq!(&vec![PushLeft, Up], lstate.moves());
+    }
+    
+    #[test]
+    fn test_is_done() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!((0, 3, 3), (lstate.packs_on_targets(), lstate.total_packs(),
+                lstate.remaining()));
+        for m in vec![Down, Down, Right, Right, Right,
+                    Up, Down,Right, Up, Down, Right, Up] {
+            assert_eq!(false, lstate.is_done());
+            lstate.make_move(m);
+        }
+        assert_eq!(true, lstate.is_done());
+        assert_eq!((3, 3, 0), (lstate.packs_on_targets(), lstate.total_packs(),
+                lstate.remaining()));
+        lstate.undo_move();
+        assert_eq!((2, 3, 1), (lstate.packs_on_targets(), lstate.total_packs(),
+                lstate.remaining()));
+    }
+    
+    #[test]
+    fn test_positions() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(level.boxes(), lstate.boxes());
+        assert_eq!(level.targets(), lstate.targets());
+        assert_eq!(level.player(), Some(lstate.player()));
+        for m in vec![Down, Down, Right, Right, Right, Up] {
+            lstate.make_move(m);
+        }
+        assert_eq!((4, 3), lstate.player());
+        assert_eq!(vec![(4, 2), (5, 3), (6, 3)], lstate.boxes());
+        assert_eq!(vec![(4, 2), (5, 2), (6, 2)], lstate.targets());
+        assert_eq!(Some((4, 2, PackOnTarget)), lstate.cells().nth(2*8 + 4));
+        assert_eq!(lstate.boxes(), lstate.cells().filter(|(_, _, f)| f.is_pack())
+                .map(|(x, y, _)| (x, y)).collect::<Vec<_>>());
+    }
+    
+    #[test]
+    fn test_replay_moves() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let moves = vec![Down, Down, Right, Right, Right,
+                    Up, Down,Right, Up, Down, Right, Up];
+        let lstate = LevelState::replay_moves(&level, &moves).unwrap();
+        assert!(lstate.is_done());
+        assert_eq!(12, lstate.steps());
+        assert_eq!(3, lstate.pushes_count());
+        let solution = lstate.record_session();
+        assert_eq!(PushUp, solution[5]);
+        // recorded solution with push directions can be replayed too
+        assert!(LevelState::replay_moves(&level, &solution).unwrap().is_done());
+        
+        // illegal moves
+        assert_eq!(Err(0), LevelState::replay_moves(&level, &[Left, Right]));
+        assert_eq!(Err(2), LevelState::replay_moves(&level, &[Down, Right, PushRight]));
+        assert_eq!(Err(1), LevelState::replay_moves(&level, &[Down, NoDirection]));
+        let empty = LevelState::replay_moves(&level, &[]).unwrap();
+        assert_eq!(0, empty.steps());
+    }
+    
+    #[test]
+    fn test_apply_lurd() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(12, lstate.apply_lurd("ddrrrUdrUdrU"));
+        assert!(lstate.is_done());
+        assert_eq!(Some("ddrrrUdrUdrU".to_string()), lstate.moves_as_lurd());
+        let mut lstate2 = LevelState::new(&level).unwrap();
+        assert!(!lstate2.make_move(NoDirection).0);
+        assert_eq!(Some(String::new()), lstate2.moves_as_lurd());
+        assert_eq!(12, lstate2.apply_lurd(&lstate.moves_as_lurd().unwrap()));
+        assert_eq!(lstate, lstate2);
+        
+        let mut lstate = LevelState::new(&level).unwrap();
+        // push expected but no push
+        assert_eq!(2, lstate.apply_lurd("ddRrrU"));
+        assert_eq!(&vec![Down, Down], lstate.moves());
+        // move expected but push
+        lstate.reset();
+        assert_eq!(5, lstate.apply_lurd("ddrrrudrUdr"));
+        // wrong character
+        lstate.reset();
+        assert_eq!(3, lstate.apply_lurd("ddrxrU"));
+        lstate.reset();
+        assert_eq!(3, lstate.apply_moves(&[Down, Down, Right, PushRight]));
+        assert!(!lstate.is_done());
+    }
+    
+    #[test]
+    fn test_moves_since() {
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        for m in vec![Down, Down, Right, Right, Right] {
+            lstate.make_move(m);
+        }
+        assert_eq!(Some(&[Right, Right, Right][..]), lstate.moves_since(2));
+        assert_eq!(Some(&lstate.moves()[..]), lstate.moves_since(0));
+        let empty: &[Direction] = &[];
+        assert_eq!(Some(empty), lstate.moves_since(5));
+        assert_eq!(Some(empty), lstate.moves_since(10));
+    }
+    
+    #[test]
+    fn test_push_chain() {
+        let level = Level::from_str("chain", 8, 5,
+            "########\
+             #      #\
+             #@$$ ..#\
+             #      #\
+             ########").unwrap();
+        let lstate = LevelState::new(&level).unwrap();
+        assert_eq!(Rules{ push_chain: 1 }, lstate.rules());
+        assert_eq!(Err(BlockedReason::BoxAgainstBox), lstate.peek_move(Right));
+        assert_ne!(lstate, LevelState::new_with_rules(&level, Rules{ push_chain: 2 }).unwrap());
+        
+        let rules = Rules{ push_chain: 2 };
+        let mut lstate = LevelState::new_with_rules(&level, rules).unwrap();
+        let orig = lstate.clone();
+        assert_eq!(rules, lstate.rules());
+        assert!(lstate.can_push_box(2, 2, Right));
+        assert!(!LevelState::new(&level).unwrap().can_push_box(2, 2, Right));
+        let result = lstate.step(Right);
+        assert_eq!(Ok(PushRight), result.outcome);
+        assert!(result.events.contains(&StateEvent::PackPushed{ from: (2, 2), to: (4, 2) }));
+        assert_eq!(1, lstate.pushes_count());
+        // every pack in chain is credited with push
+        assert_eq!(1, lstate.pushes_for_box(0));
+        assert_eq!(1, lstate.pushes_for_box(1));
+        assert_eq!(vec![(4, 2), (1, 2), (2, 2)], lstate.changed_cells().to_vec());
+        assert_eq!(Ok(PushRight), lstate.try_move(Right));
+        assert_eq!(Ok(PushRight), lstate.try_move(Right));
+        assert!(lstate.is_done());
+        assert_eq!(Err(BlockedReason::BoxAgainstWall), lstate.peek_move(Right));
+        for _ in 0..3 {
+            assert!(lstate.undo_move());
+        }
+        assert_eq!(orig, lstate);
+        
+        let level = Level::from_str("chain3", 8, 5,
+            "########\
+             #      #\
+             #@$$$..#\
+             #   .  #\
+             ########").unwrap();
+        let lstate = LevelState::new_with_rules(&level, rules).unwrap();
+        assert_eq!(Err(BlockedReason::BoxAgainstBox), lstate.peek_move(Right));
+        assert!(!lstate.can_push_box(2, 2, Right));
+        assert_eq!(Some(&vec![Up, Down]), lstate.actionable_boxes().iter()
+                .find(|(pos, _)| *pos == (2, 2)).map(|(_, dirs)| dirs));
+        
+        // zero push chain means single push
+        let lstate = LevelState::new_with_rules(&level, Rules{ push_chain: 0 }).unwrap();
+        assert_eq!(Rules::default(), lstate.rules());
+        assert_eq!(LevelState::new(&level).unwrap(), lstate);
+    }
+    
+    #[test]
+    fn test_pull_move() {
+        let level = Level::from_str("pull", 7, 5,
+            "#######\
+             #     #\
+             # $+  #\
+             #     #\
+             #######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let orig = lstate.clone();
+        // pack in direction of move
+        assert_eq!((false, false), lstate.pull_move(Left));
+        assert_eq!(Err(BlockedReason::Unsupported), lstate.peek_move(PullRight));
+        assert_eq!((true, true), lstate.pull_move(Right));
+        assert_eq!(PackOnTarget, lstate.area()[2*7 + 3]);
+        assert_eq!(Empty, lstate.area()[2*7 + 2]);
+        assert_eq!((4, 2), lstate.player());
+        assert!(lstate.is_done());
+        assert_eq!(&vec![PullRight], lstate.moves());
+        assert_eq!(0, lstate.pushes_count());
+        assert_eq!(0, lstate.pushes_for_box(0));
+        assert_eq!(vec![(2, 2), (3, 2), (4, 2)], lstate.changed_cells().to_vec());
+        assert_eq!(Some(PullRight), lstate.undo_move_dir());
+        assert_eq!(orig, lstate);
+        assert!(lstate.redo_move());
+        assert!(lstate.is_done());
+        assert_eq!(Ok(lstate.clone()), LevelState::replay_moves(&level, &[PullRight]));
+        assert_eq!(Err(0), LevelState::replay_moves(&level, &[PullUp]).map(|_| ()));
+        // pulled pack keeps its pushes
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert_eq!(1, lstate.pushes_for_box(0));
+        assert!(lstate.undo_move());
+        assert!(lstate.undo_move());
+        assert_eq!(0, lstate.pushes_for_box(0));
+        assert_eq!(orig, lstate);
+        
+        // no pack behind player - simple move
+        let mut lstate = orig.clone();
+        assert_eq!((true, false), lstate.pull_move(Up));
+        assert_eq!(&vec![Up], lstate.moves());
+        // wall in direction of move
+        assert_eq!((false, false), lstate.pull_move(Up));
+        
+        // pull pack from target to target and undo
+        let level = Level::from_str("pull2", 7, 5,
+            "#######\
+             #     #\
+             #$*+  #\
+             #     #\
+             #######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let orig = lstate.clone();
+        lstate.record(true);
+        assert_eq!((true, true), lstate.pull_move(Right));
+        assert_eq!(&[Pack, Target, PackOnTarget, Player], &lstate.area()[2*7+1..2*7+5]);
+        assert_eq!(None, lstate.moves_as_lurd());
+        assert!(lstate.undo_move());
+        assert_eq!(&orig.area()[..], &lstate.area()[..]);
+        assert_eq!(PlayerOnTarget, lstate.area()[2*7 + 3]);
+        assert_eq!((3, 2), lstate.player());
+        assert_eq!(Some(String::new()), lstate.moves_as_lurd());
+        // pull and push back
+        assert_eq!((true, true), lstate.pull_move(Right));
+        assert_eq!((true, true), lstate.make_move(Left));
+        assert_eq!(&vec![PullRight, PushLeft], lstate.moves());
+        assert_eq!(None, lstate.moves_as_lurd());
+        assert!(lstate.undo_move());
+        assert!(lstate.undo_move());
+        assert_eq!(&orig.area()[..], &lstate.area()[..]);
+        
+        // pulls in sokrec format
+        let session = lstate.export_session().unwrap();
+        let mut bytes = vec![];
+        session.write_sokrec(&mut bytes).unwrap();
+        assert!(std::str::from_utf8(&bytes).unwrap().ends_with("~r-~rL--\n"));
+        let session2 = Session::read_sokrec(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(session, session2);
+        let played = LevelState::play_session(&level, &session2).unwrap();
+        assert_eq!(lstate.area(), played.area());
+        for bad in [&b"7 1\n#$*+  #\n~\n"[..], &b"7 1\n#$*+  #\n~L\n"[..]] {
+            assert!(Session::read_sokrec(&mut std::io::Cursor::new(bad)).is_err());
+        }
     }
 }
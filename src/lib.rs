@@ -26,8 +26,17 @@ pub use self::level::*;
 mod level_state;
 pub use self::level_state::*;
 
+mod solver;
+pub use self::solver::*;
+
+mod session;
+pub use self::session::*;
+
 mod level_set;
 pub use self::level_set::*;
 
+mod progress;
+pub use self::progress::*;
+
 mod term_game;
 pub use self::term_game::*;
@@ -23,20 +23,114 @@ use sokobanlib::*;
 use termion::raw::IntoRawMode;
 use termion::cursor;
 
-fn main() {
-    let mut args = env::args();
-    if args.len() < 2 {
-        eprintln!("No file");
-        std::process::exit(1);
+// check all levels from levelset and print report. Return true if all levels are valid.
+fn check_levelset(levelset: &LevelSet, solvable: bool) -> bool {
+    let mut invalid = 0;
+    for (i, lr) in levelset.levels().iter().enumerate() {
+        match lr {
+            Ok(level) => match level.check() {
+                Ok(()) => {
+                    if solvable {
+                        match level.is_solvable() {
+                            Some(true) => println!("{}: {}: OK", i+1, level.name()),
+                            Some(false) => {
+                                println!("{}: {}: Unsolvable", i+1, level.name());
+                                invalid += 1;
+                            }
+                            None => println!("{}: {}: OK, solvability unknown",
+                                        i+1, level.name()),
+                        }
+                    } else {
+                        println!("{}: {}: OK", i+1, level.name());
+                    }
+                }
+                Err(errors) => {
+                    println!("{}: {}: {}", i+1, level.name(), errors);
+                    invalid += 1;
+                }
+            }
+            Err(e) => {
+                println!("{}: {}", i+1, e);
+                invalid += 1;
+            }
+        }
+    }
+    println!("Checked {} levels, {} invalid", levelset.levels().len(), invalid);
+    invalid == 0
+}
+
+// solve all levels from levelset and print solutions in LURD format.
+fn solve_levelset(levelset: &LevelSet) {
+    for (i, lr) in levelset.levels().iter().enumerate() {
+        match lr {
+            Ok(level) => match level.solve_bounded() {
+                SolveOutcome::Solved(moves) => {
+                    let lurd: String = moves.iter().filter_map(|d| d.to_lurd()).collect();
+                    println!("{}: {}: {}", i+1, level.name(), lurd);
+                }
+                SolveOutcome::Unsolvable =>
+                    println!("{}: {}: unsolvable", i+1, level.name()),
+                SolveOutcome::LimitReached =>
+                    println!("{}: {}: timed out", i+1, level.name()),
+            }
+            Err(e) => println!("{}: {}", i+1, e),
+        }
     }
-    args.next();
-    let levelset_path = args.next().unwrap();
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let check = args.iter().any(|a| a == "--check");
+    let solve = args.iter().any(|a| a == "--solve");
+    let solvable = args.iter().any(|a| a == "--solvable");
+    let progress_path = args.iter().find_map(|a| a.strip_prefix("--progress="));
+    let levelset_path = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => path,
+        None => {
+            eprintln!("No file");
+            std::process::exit(1);
+        }
+    };
     match LevelSet::from_file(levelset_path) {
         Ok(levelset) => {
+            if check {
+                if !check_levelset(&levelset, solvable) {
+                    std::process::exit(2);
+                }
+                return;
+            }
+            if solve {
+                solve_levelset(&levelset);
+                return;
+            }
+            let progress = match progress_path.map(Progress::load).transpose() {
+                Ok(progress) => progress,
+                Err(err) => {
+                    eprintln!("Some error during loading progress: {}", err);
+                    std::process::exit(1);
+                }
+            };
             let stdout = io::stdout().into_raw_mode().unwrap();
             let mut stdout = cursor::HideCursor::from(stdout);
             let mut term_levelset = TermLevelSet::create(&mut stdout, &levelset);
-            term_levelset.start().unwrap();
+            if let Some(progress) = progress {
+                term_levelset.set_progress(progress);
+            }
+            let result = term_levelset.start();
+            let progress = term_levelset.progress().clone();
+            // restore terminal before printing errors
+            drop(term_levelset);
+            drop(stdout);
+            if let Some(path) = progress_path {
+                if let Err(err) = progress.save(path) {
+                    eprintln!("Some error during saving progress: {}", err);
+                    std::process::exit(1);
+                }
+            }
+            if let Err(err) = result {
+                eprintln!("Some error during game: {}", err);
+                std::process::exit(1);
+            }
         }
         Err(err) => {
             eprintln!("Some error during loading levelset: {}", err);
@@ -0,0 +1,216 @@
+// progress.rs - main library of sokoban
+//
+// sokoban - Sokoban game
+// Copyright (C) 2022  Mateusz Szpakowski
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use std::io;
+use std::io::{BufRead,BufReader,Write};
+use std::fs::File;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+/// Best result of solved level - number of moves and pushes.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct LevelRecord {
+    /// Number of moves (all steps including pushes).
+    pub moves: usize,
+    /// Number of pushes.
+    pub pushes: usize,
+}
+
+/// Progress of player - solved levels and their best results keyed by
+/// name of levelset and index of level.
+///
+/// Progress file is text file. Every line contains one record with fields
+/// separated by tab. Record of solved level has form:
+/// `solved<TAB>levelset name<TAB>level index<TAB>moves<TAB>pushes`.
+/// Backslash, tab and newlines in levelset name are escaped as `\\`, `\t`,
+/// `\n` and `\r`.
+/// Empty lines, lines beginning with '#', records of other kinds and fields
+/// after pushes are ignored, so newer versions can add records and fields.
+#[derive(PartialEq,Eq,Debug,Clone,Default)]
+pub struct Progress {
+    records: BTreeMap<(String, usize), LevelRecord>,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// escape characters that can't be stored in field
+fn escape_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape_name(field: &str) -> io::Result<String> {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            _ => return Err(invalid_data("Bad escape in name")),
+        }
+    }
+    Ok(out)
+}
+
+impl Progress {
+    /// Create empty progress.
+    pub fn new() -> Progress {
+        Progress{ records: BTreeMap::new() }
+    }
+    
+    /// Return best result of level or None if level has not been solved.
+    pub fn record(&self, levelset: &str, index: usize) -> Option<LevelRecord> {
+        self.records.get(&(levelset.to_string(), index)).copied()
+    }
+    
+    /// Return true if level has been solved.
+    pub fn is_solved(&self, levelset: &str, index: usize) -> bool {
+        self.record(levelset, index).is_some()
+    }
+    
+    /// Return index of first unsolved level from levels_num levels.
+    pub fn first_unsolved(&self, levelset: &str, levels_num: usize) -> Option<usize> {
+        (0..levels_num).find(|i| !self.is_solved(levelset, *i))
+    }
+    
+    /// Mark level as solved with given numbers of moves and pushes. Best result
+    /// (fewer moves, then fewer pushes) is kept. Return true if result is new best.
+    pub fn set_solved(&mut self, levelset: &str, index: usize, moves: usize,
+                    pushes: usize) -> bool {
+        let key = (levelset.to_string(), index);
+        match self.records.get(&key) {
+            Some(r) if (r.moves, r.pushes) <= (moves, pushes) => false,
+            _ => {
+                self.records.insert(key, LevelRecord{ moves, pushes });
+                true
+            }
+        }
+    }
+    
+    /// Write progress to writer.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "# sokoban progress")?;
+        for ((levelset, index), r) in &self.records {
+            writeln!(w, "solved\t{}\t{}\t{}\t{}", escape_name(levelset), index,
+                    r.moves, r.pushes)?;
+        }
+        Ok(())
+    }
+    
+    /// Read progress from reader.
+    pub fn read<R: BufRead>(r: &mut R) -> io::Result<Progress> {
+        let mut progress = Progress::new();
+        for rl in r.lines() {
+            let l = rl?;
+            let fields: Vec<&str> = l.split('\t').collect();
+            if fields[0] != "solved" {
+                continue; // comment or unknown record
+            }
+            if fields.len() < 5 {
+                return Err(invalid_data("Too few fields"));
+            }
+            let nums = fields[2..5].iter().map(|x| x.parse::<usize>()
+                    .map_err(|_| invalid_data("Bad number")))
+                    .collect::<io::Result<Vec<_>>>()?;
+            progress.records.insert((unescape_name(fields[1])?, nums[0]),
+                    LevelRecord{ moves: nums[1], pushes: nums[2] });
+        }
+        Ok(progress)
+    }
+    
+    /// Save progress to file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut f = File::create(path)?;
+        self.write(&mut f)
+    }
+    
+    /// Load progress from file. Return empty progress if file doesn't exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Progress> {
+        match File::open(path) {
+            Ok(f) => Self::read(&mut BufReader::new(f)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Progress::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[test]
+    fn test_progress() {
+        let mut progress = Progress::new();
+        assert!(!progress.is_solved("Microban", 0));
+        assert_eq!(Some(0), progress.first_unsolved("Microban", 3));
+        assert!(progress.set_solved("Microban", 0, 20, 5));
+        assert!(!progress.set_solved("Microban", 0, 22, 4));
+        assert!(!progress.set_solved("Microban", 0, 20, 5));
+        assert!(progress.set_solved("Microban", 0, 20, 4));
+        assert!(progress.set_solved("Microban", 1, 40, 10));
+        assert!(progress.set_solved("Other\tset", 0, 7, 1));
+        assert_eq!(Some(LevelRecord{ moves: 20, pushes: 4 }), progress.record("Microban", 0));
+        assert_eq!(Some(2), progress.first_unsolved("Microban", 3));
+        assert_eq!(None, progress.first_unsolved("Microban", 2));
+        
+        let mut out = vec![];
+        progress.write(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!("# sokoban progress\nsolved\tMicroban\t0\t20\t4\n\
+                solved\tMicroban\t1\t40\t10\nsolved\tOther\\tset\t0\t7\t1\n", out);
+        let read = Progress::read(&mut io::Cursor::new(out.as_bytes())).unwrap();
+        assert_eq!(progress, read);
+        assert_eq!(Some(LevelRecord{ moves: 40, pushes: 10 }), read.record("Microban", 1));
+        assert!(read.is_solved("Other\tset", 0));
+        assert!(!read.is_solved("Other set", 0));
+        
+        let mut progress = Progress::new();
+        progress.set_solved("A\\b\nc\r", 0, 1, 1);
+        let mut out = vec![];
+        progress.write(&mut out).unwrap();
+        assert_eq!(progress, Progress::read(&mut io::Cursor::new(out)).unwrap());
+        
+        // unknown records and fields are ignored
+        let read = Progress::read(&mut io::Cursor::new(
+                "# comment\n\nbest\tMicroban\t3\nsolved\tMicroban\t2\t5\t1\textra\n"
+                .as_bytes())).unwrap();
+        assert_eq!(Some(LevelRecord{ moves: 5, pushes: 1 }), read.record("Microban", 2));
+        assert!(!read.is_solved("Microban", 3));
+        assert!(Progress::read(&mut io::Cursor::new(b"solved\tMicroban\t2\t5\n")).is_err());
+        assert!(Progress::read(&mut io::Cursor::new(b"solved\tMicroban\tx\t5\t1\n")).is_err());
+        assert!(Progress::read(&mut io::Cursor::new(b"solved\tMicro\\ban\t2\t5\t1\n")).is_err());
+    }
+}
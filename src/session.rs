@@ -0,0 +1,142 @@
+// session.rs - main library of sokoban
+//
+// sokoban - Sokoban game
+// Copyright (C) 2022  Mateusz Szpakowski
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use std::io;
+use std::io::{BufRead,Write};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize,Deserialize};
+
+use crate::defs::*;
+
+/// Event of recorded session.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize,Deserialize))]
+pub enum SessionEvent {
+    /// Move of player (direction of done move).
+    Move(Direction),
+    /// Undo of last move.
+    Undo,
+    /// Reset of level state.
+    Reset,
+    /// Storing checkpoint.
+    Checkpoint,
+    /// Rollback to last checkpoint.
+    Rollback,
+}
+
+/// Recorded session - initial board and all events (moves, undos, resets,
+/// checkpoints and rollbacks).
+#[derive(PartialEq,Eq,Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize,Deserialize))]
+pub struct Session {
+    /// Width of the board.
+    pub width: usize,
+    /// Height of the board.
+    pub height: usize,
+    /// Initial board.
+    pub area: Vec<Field>,
+    /// Events in order.
+    pub events: Vec<SessionEvent>,
+}
+
+/// Maximal number of fields of board in sokrec format.
+const SOKREC_MAX_AREA: usize = 1 << 20;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Session {
+    /// Write session in sokrec format: first line contains width and height,
+    /// next lines contain rows of the board and last line contains events:
+    /// moves in LURD format, pulls as '~' and LURD move (for example "~l" -
+    /// pull left), '-' - undo, '!' - reset, '=' - checkpoint, '<' - rollback.
+    pub fn write_sokrec<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{} {}", self.width, self.height)?;
+        for row in self.area.chunks(self.width.max(1)) {
+            let line: String = row.iter().map(|f| f.to_char()).collect();
+            writeln!(w, "{}", line)?;
+        }
+        let mut events = String::new();
+        for e in &self.events {
+            match e {
+                SessionEvent::Move(d) if d.is_pull() => {
+                    events.push('~');
+                    events.extend(d.as_move().to_lurd());
+                }
+                SessionEvent::Move(d) => match d.to_lurd() {
+                    Some(c) => events.push(c),
+                    None => return Err(invalid_data("Bad move")),
+                },
+                SessionEvent::Undo => events.push('-'),
+                SessionEvent::Reset => events.push('!'),
+                SessionEvent::Checkpoint => events.push('='),
+                SessionEvent::Rollback => events.push('<'),
+            }
+        }
+        writeln!(w, "{}", events)
+    }
+    
+    /// Read session in sokrec format.
+    pub fn read_sokrec<R: BufRead>(r: &mut R) -> io::Result<Session> {
+        let mut lines = r.lines();
+        let header = lines.next().ok_or_else(|| invalid_data("No header"))??;
+        let dims: Vec<usize> = header.split_whitespace()
+                .map(|x| x.parse().map_err(|_| invalid_data("Bad dimensions")))
+                .collect::<io::Result<_>>()?;
+        if dims.len() != 2 {
+            return Err(invalid_data("Bad dimensions"));
+        }
+        let (width, height) = (dims[0], dims[1]);
+        let size = width.checked_mul(height).filter(|size| *size <= SOKREC_MAX_AREA)
+                .ok_or_else(|| invalid_data("Bad dimensions"))?;
+        let mut area = Vec::with_capacity(size);
+        for _ in 0..height {
+            let line = lines.next().ok_or_else(|| invalid_data("Too few rows"))??;
+            if line.chars().count() != width || line.chars().any(is_not_field) {
+                return Err(invalid_data("Bad row"));
+            }
+            area.extend(line.chars().map(char_to_field));
+        }
+        let mut events = vec![];
+        if let Some(line) = lines.next() {
+            let line = line?;
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                events.push(match c {
+                    '-' => SessionEvent::Undo,
+                    '!' => SessionEvent::Reset,
+                    '=' => SessionEvent::Checkpoint,
+                    '<' => SessionEvent::Rollback,
+                    '~' => match chars.next() {
+                        Some('l') => SessionEvent::Move(Direction::PullLeft),
+                        Some('r') => SessionEvent::Move(Direction::PullRight),
+                        Some('u') => SessionEvent::Move(Direction::PullUp),
+                        Some('d') => SessionEvent::Move(Direction::PullDown),
+                        _ => return Err(invalid_data("Bad event")),
+                    },
+                    c => Direction::from_lurd(c).map(SessionEvent::Move)
+                            .ok_or_else(|| invalid_data("Bad event"))?,
+                });
+            }
+        }
+        Ok(Session{ width, height, area, events })
+    }
+}
@@ -0,0 +1,842 @@
+// solver.rs - main library of sokoban
+//
+// sokoban - Sokoban game
+// Copyright (C) 2022  Mateusz Szpakowski
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap,HashMap,VecDeque};
+use std::time::{Duration,Instant};
+
+use crate::defs::*;
+
+use crate::{Level,LevelSet,LevelState};
+use Field::*;
+use Direction::*;
+
+// key of solver state - player position and sorted positions of packs.
+#[derive(PartialEq,Eq,Hash,Clone,Debug)]
+struct StateKey {
+    player: usize,
+    packs: Vec<usize>,
+}
+
+// node of search - state, index of parent node and move from parent node.
+struct Node {
+    key: StateKey,
+    parent: usize,
+    dir: Direction,
+}
+
+// solver core - contains static parts of level and visited nodes.
+struct SolverCore {
+    width: usize,
+    height: usize,
+    walls: Vec<bool>,
+    targets: Vec<bool>,
+    // fields from that pack never can be pushed to any target.
+    dead: Vec<bool>,
+    nodes: Vec<Node>,
+    expanded: usize,
+    deadline: Option<Instant>,
+    timed_out: bool,
+    node_limit: Option<usize>,
+    limit_reached: bool,
+    // maximal number of nodes waiting for expansion.
+    max_frontier: usize,
+    // maximal number of packs pushed by one move.
+    push_chain: usize,
+}
+
+/// Maximal number of expanded nodes while proving solvability of level.
+pub(crate) const SOLVABLE_NODE_LIMIT: usize = 1000000;
+
+/// Maximal number of expanded nodes while estimating difficulty of level.
+const DIFFICULTY_NODE_LIMIT: usize = 1000000;
+
+impl SolverCore {
+    fn new(level: &Level) -> SolverCore {
+        let mut core = SolverCore{ width: level.width, height: level.height,
+            walls: level.area.iter().map(|x| *x == Wall).collect(),
+            targets: level.area.iter().map(|x| x.is_target()).collect(),
+            dead: vec![], nodes: vec![], expanded: 0, deadline: None, timed_out: false,
+            node_limit: None, limit_reached: false, max_frontier: 0,
+            push_chain: Rules::default().push_chain };
+        core.dead = core.dead_squares();
+        core
+    }
+    
+    // return dead squares. Fields from that pack can be pushed to target are found
+    // by pulling pack from every target: pack can be pulled to next field if
+    // field after it (where player stands) is not wall.
+    fn dead_squares(&self) -> Vec<bool> {
+        let mut alive = self.targets.clone();
+        let mut stk: Vec<usize> = (0..alive.len()).filter(|p| alive[*p]).collect();
+        while let Some(pos) = stk.pop() {
+            for d in Direction::all_moves() {
+                let next = self.next_pos(pos, d).filter(|p| !self.walls[*p]);
+                let player = next.and_then(|p| self.next_pos(p, d))
+                        .filter(|p| !self.walls[*p]);
+                if let (Some(next), Some(_)) = (next, player) {
+                    if !alive[next] {
+                        alive[next] = true;
+                        stk.push(next);
+                    }
+                }
+            }
+        }
+        self.walls.iter().zip(alive).map(|(w, a)| !w && !a).collect()
+    }
+    
+    // return next position after move in direction.
+    fn next_pos(&self, pos: usize, dir: Direction) -> Option<usize> {
+        let x = pos % self.width;
+        let y = pos / self.width;
+        match dir {
+            Left|PushLeft|PullLeft => if x > 0 { Some(pos-1) } else { None },
+            Right|PushRight|PullRight => if x+1 < self.width { Some(pos+1) } else { None },
+            Up|PushUp|PullUp => if y > 0 { Some(pos-self.width) } else { None },
+            Down|PushDown|PullDown => if y+1 < self.height { Some(pos+self.width) } else { None },
+            NoDirection => None,
+        }
+    }
+    
+    // return true if all packs on targets.
+    fn is_done(&self, key: &StateKey) -> bool {
+        key.packs.iter().all(|p| self.targets[*p])
+    }
+    
+    // try to make move from state. Return new state and done move.
+    fn make_move(&self, key: &StateKey, dir: Direction) -> Option<(StateKey, Direction)> {
+        let next = self.next_pos(key.player, dir)?;
+        if self.walls[next] { return None; }
+        if let Ok(i) = key.packs.binary_search(&next) {
+            // push chain of packs - it moves like first pack jumps after last pack
+            let mut chain = 1;
+            let mut next2 = self.next_pos(next, dir)?;
+            while key.packs.binary_search(&next2).is_ok() {
+                chain += 1;
+                if chain > self.push_chain { return None; }
+                next2 = self.next_pos(next2, dir)?;
+            }
+            if self.walls[next2] || self.dead[next2] {
+                return None;
+            }
+            let mut packs = key.packs.clone();
+            packs[i] = next2;
+            packs.sort_unstable();
+            let push_dir = match dir {
+                Left => PushLeft,
+                Right => PushRight,
+                Up => PushUp,
+                _ => PushDown,
+            };
+            Some((StateKey{ player: next, packs }, push_dir))
+        } else {
+            Some((StateKey{ player: next, packs: key.packs.clone() }, dir))
+        }
+    }
+    
+    // return start state of level or None if level never can be done.
+    fn start_key(&self, level: &Level) -> Option<StateKey> {
+        let player = level.area.iter().position(|x| x.is_player())?;
+        let packs: Vec<usize> = level.area.iter().enumerate()
+                .filter(|(_,x)| x.is_pack()).map(|(i,_)| i).collect();
+        let targets_num = self.targets.iter().filter(|x| **x).count();
+        if packs.len() != targets_num || packs.iter().any(|p| self.dead[*p]) {
+            return None;    // never can be done
+        }
+        Some(StateKey{ player, packs })
+    }
+    
+    // count expanded node and check time budget and node limit.
+    // Return true if search must be stopped.
+    fn expand_exceeds_budget(&mut self) -> bool {
+        // check time budget before first expansion and after every 1024 expansions
+        if let Some(deadline) = self.deadline {
+            if (self.expanded & 1023) == 0 && Instant::now() >= deadline {
+                self.timed_out = true;
+                return true;
+            }
+        }
+        self.expanded += 1;
+        // check node limit
+        if let Some(node_limit) = self.node_limit {
+            if self.expanded > node_limit {
+                self.limit_reached = true;
+                return true;
+            }
+        }
+        false
+    }
+    
+    // lower bound of number of moves to solution - sum of manhattan distances
+    // from packs to nearest targets.
+    fn lower_bound(&self, key: &StateKey) -> usize {
+        key.packs.iter().map(|p| {
+            let (px, py) = (p % self.width, p / self.width);
+            self.targets.iter().enumerate().filter(|(_, t)| **t).map(|(t, _)| {
+                let (tx, ty) = (t % self.width, t / self.width);
+                px.max(tx) - px.min(tx) + py.max(ty) - py.min(ty)
+            }).min().unwrap_or(0)
+        }).sum()
+    }
+    
+    // breadth-first search. Return index of node with solution.
+    fn search(&mut self, level: &Level) -> Option<usize> {
+        let start = self.start_key(level)?;
+        let mut visited = HashMap::new();
+        let mut queue = VecDeque::new();
+        visited.insert(start.clone(), 0);
+        self.nodes.push(Node{ key: start, parent: 0, dir: NoDirection });
+        queue.push_back(0);
+        
+        while let Some(ni) = queue.pop_front() {
+            if self.is_done(&self.nodes[ni].key) {
+                return Some(ni);
+            }
+            if self.expand_exceeds_budget() {
+                return None;
+            }
+            for d in Direction::all_moves() {
+                if let Some((key, dir)) = self.make_move(&self.nodes[ni].key, d) {
+                    if !visited.contains_key(&key) {
+                        let nni = self.nodes.len();
+                        visited.insert(key.clone(), nni);
+                        self.nodes.push(Node{ key, parent: ni, dir });
+                        queue.push_back(nni);
+                        self.max_frontier = self.max_frontier.max(queue.len());
+                    }
+                }
+            }
+        }
+        None
+    }
+    
+    // A* search with lower bound as heuristic. Return index of node with solution.
+    fn search_astar(&mut self, level: &Level) -> Option<usize> {
+        let start = self.start_key(level)?;
+        // best number of moves to reach state
+        let mut visited = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((self.lower_bound(&start), 0, 0)));
+        visited.insert(start.clone(), 0);
+        self.nodes.push(Node{ key: start, parent: 0, dir: NoDirection });
+        
+        while let Some(Reverse((_, cost, ni))) = heap.pop() {
+            if visited.get(&self.nodes[ni].key) != Some(&cost) {
+                continue;   // better path found later
+            }
+            if self.is_done(&self.nodes[ni].key) {
+                return Some(ni);
+            }
+            if self.expand_exceeds_budget() {
+                return None;
+            }
+            for d in Direction::all_moves() {
+                if let Some((key, dir)) = self.make_move(&self.nodes[ni].key, d) {
+                    if visited.get(&key).is_none_or(|c| cost+1 < *c) {
+                        let nni = self.nodes.len();
+                        let estimate = cost+1 + self.lower_bound(&key);
+                        visited.insert(key.clone(), cost+1);
+                        self.nodes.push(Node{ key, parent: ni, dir });
+                        heap.push(Reverse((estimate, cost+1, nni)));
+                        self.max_frontier = self.max_frontier.max(heap.len());
+                    }
+                }
+            }
+        }
+        None
+    }
+    
+    // return moves from start to node.
+    fn moves(&self, mut ni: usize) -> Vec<Direction> {
+        let mut moves = vec![];
+        while ni != 0 {
+            moves.push(self.nodes[ni].dir);
+            ni = self.nodes[ni].parent;
+        }
+        moves.reverse();
+        moves
+    }
+    
+    // return number of moves from start to node.
+    fn moves_count(&self, mut ni: usize) -> usize {
+        let mut count = 0;
+        while ni != 0 {
+            count += 1;
+            ni = self.nodes[ni].parent;
+        }
+        count
+    }
+}
+
+/// Outcome of solving level with limited search.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub enum SolveOutcome {
+    /// Level solved - moves of optimal solution.
+    Solved(Vec<Direction>),
+    /// Level can not be solved.
+    Unsolvable,
+    /// Limit of search has been reached before finding solution.
+    LimitReached,
+}
+
+/// Result of searching hint.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Hint {
+    /// Next move of solution (push direction if move pushes pack).
+    Move(Direction),
+    /// Level is already solved.
+    Done,
+    /// Level can not be solved from current state.
+    Unsolvable,
+    /// Limit of search has been reached before finding solution.
+    LimitReached,
+}
+
+/// Metrics of solving level.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct SolveStats {
+    /// Length of optimal solution or None if limit of search has been reached.
+    pub solution_len: Option<usize>,
+    /// Number of expanded nodes.
+    pub nodes_expanded: usize,
+    /// Maximal number of nodes waiting for expansion.
+    pub max_frontier: usize,
+}
+
+/// Estimated difficulty of level.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Difficulty {
+    /// Easy level.
+    Easy,
+    /// Medium level.
+    Medium,
+    /// Hard level.
+    Hard,
+    /// Difficulty unknown - limit of search has been reached.
+    Unknown,
+}
+
+impl Level {
+    /// Return dead squares - fields (not walls) from that no pack can be pushed
+    /// to any target. Solver never pushes pack to dead square.
+    pub fn dead_squares(&self) -> Vec<bool> {
+        SolverCore::new(self).dead
+    }
+    
+    /// Solve level. Return moves of the optimal solution (with minimal number of moves)
+    /// or None if level can not be solved.
+    pub fn solve(&self) -> Option<Vec<Direction>> {
+        let mut core = SolverCore::new(self);
+        core.search(self).map(|ni| core.moves(ni))
+    }
+    
+    /// Return number of moves of the optimal solution or None if level can not be solved.
+    pub fn solution_length(&self) -> Option<usize> {
+        let mut core = SolverCore::new(self);
+        core.search(self).map(|ni| core.moves_count(ni))
+    }
+    
+    /// Return Some(true) if level can be solved, Some(false) if whole state space
+    /// has been searched without solution or None if limit of search has been reached.
+    pub fn is_solvable(&self) -> Option<bool> {
+        self.is_solvable_with_limit(SOLVABLE_NODE_LIMIT)
+    }
+    
+    // check solvability with given maximal number of expanded nodes.
+    fn is_solvable_with_limit(&self, node_limit: usize) -> Option<bool> {
+        match self.solve_with_limit(node_limit) {
+            SolveOutcome::Solved(_) => Some(true),
+            SolveOutcome::Unsolvable => Some(false),
+            SolveOutcome::LimitReached => None,
+        }
+    }
+    
+    /// Solve level with limited search. Return outcome of solving.
+    pub fn solve_bounded(&self) -> SolveOutcome {
+        self.solve_with_limit(SOLVABLE_NODE_LIMIT)
+    }
+    
+    /// Solve level with given maximal number of expanded nodes.
+    /// Return outcome of solving.
+    pub fn solve_with_limit(&self, node_limit: usize) -> SolveOutcome {
+        self.solve_with_rules(node_limit, Rules::default())
+    }
+    
+    // solve level with given maximal number of expanded nodes and rules of game.
+    fn solve_with_rules(&self, node_limit: usize, rules: Rules) -> SolveOutcome {
+        let mut core = SolverCore::new(self);
+        core.node_limit = Some(node_limit);
+        core.push_chain = rules.push_chain;
+        if let Some(ni) = core.search(self) {
+            SolveOutcome::Solved(core.moves(ni))
+        } else if core.limit_reached {
+            SolveOutcome::LimitReached
+        } else { SolveOutcome::Unsolvable }
+    }
+    
+    /// Solve level with given maximal number of expanded nodes and return metrics
+    /// of solving or None if level can not be solved.
+    pub fn solve_stats(&self, node_limit: usize) -> Option<SolveStats> {
+        let mut core = SolverCore::new(self);
+        core.node_limit = Some(node_limit);
+        let solution_len = core.search(self).map(|ni| core.moves_count(ni));
+        if solution_len.is_none() && !core.limit_reached {
+            return None;
+        }
+        Some(SolveStats{ solution_len, nodes_expanded: core.expanded,
+                max_frontier: core.max_frontier })
+    }
+    
+    /// Estimate difficulty of level from number of packs, length of solution
+    /// and number of expanded nodes. Level is easy if solver expands fewer than
+    /// 10000 nodes, solution is shorter than 100 moves and level has at most 4 packs.
+    /// Level is hard if solver expands at least 200000 nodes or solution has
+    /// at least 300 moves. Return None if level can not be solved.
+    pub fn estimate_difficulty(&self) -> Option<Difficulty> {
+        let stats = self.solve_stats(DIFFICULTY_NODE_LIMIT)?;
+        let packs = self.area.iter().filter(|x| x.is_pack()).count();
+        Some(match stats.solution_len {
+            None => Difficulty::Unknown,
+            Some(len) if stats.nodes_expanded < 10000 && len < 100 && packs <= 4
+                => Difficulty::Easy,
+            Some(len) if stats.nodes_expanded >= 200000 || len >= 300
+                => Difficulty::Hard,
+            Some(_) => Difficulty::Medium,
+        })
+    }
+    
+    /// Solve level by using A* search with lower bound (sum of distances from packs
+    /// to nearest targets). Return moves of the optimal solution or None if level
+    /// can not be solved. Usually faster than `solve` for bigger levels.
+    pub fn solve_astar(&self) -> Option<Vec<Direction>> {
+        let mut core = SolverCore::new(self);
+        core.search_astar(self).map(|ni| core.moves(ni))
+    }
+    
+    /// Solve level by using A* search with given maximal number of expanded nodes.
+    /// Return outcome of solving.
+    pub fn solve_astar_with_limit(&self, node_limit: usize) -> SolveOutcome {
+        let mut core = SolverCore::new(self);
+        core.node_limit = Some(node_limit);
+        if let Some(ni) = core.search_astar(self) {
+            SolveOutcome::Solved(core.moves(ni))
+        } else if core.limit_reached {
+            SolveOutcome::LimitReached
+        } else { SolveOutcome::Unsolvable }
+    }
+    
+    /// Return true if moves in LURD format solve level.
+    pub fn verify_solution(&self, lurd: &str) -> bool {
+        let mut last = None;
+        for frame in LevelState::replay(self, lurd) {
+            match frame {
+                Ok(area) => last = Some(area),
+                Err(_) => return false,
+            }
+        }
+        // solved if all packs on targets
+        let area = last.as_ref().unwrap_or(&self.area);
+        !area.iter().any(|x| *x == Pack || *x == Target || *x == PlayerOnTarget)
+    }
+}
+
+impl<'a> LevelState<'a> {
+    /// Return next move of solution from current state. Search is limited by
+    /// the same number of expanded nodes as `solve_bounded` and follows rules
+    /// of level state.
+    pub fn hint(&self) -> Hint {
+        self.hint_with_limit(SOLVABLE_NODE_LIMIT)
+    }
+    
+    fn hint_with_limit(&self, node_limit: usize) -> Hint {
+        if self.is_done() {
+            return Hint::Done;
+        }
+        match self.to_level(self.level().name())
+                .solve_with_rules(node_limit, self.rules()) {
+            SolveOutcome::Solved(moves) => moves.first().copied()
+                    .map_or(Hint::Done, Hint::Move),
+            SolveOutcome::Unsolvable => Hint::Unsolvable,
+            SolveOutcome::LimitReached => Hint::LimitReached,
+        }
+    }
+}
+
+/// Result of benchmark of solving single level.
+#[derive(Debug,Clone)]
+pub struct BenchEntry {
+    /// Name of level.
+    pub name: String,
+    /// Time of solving.
+    pub time: Duration,
+    /// Number of expanded nodes.
+    pub nodes_expanded: usize,
+    /// Length of solution or None if no solution found.
+    pub solution_len: Option<usize>,
+    /// True if solving has been stopped by time budget.
+    pub timed_out: bool,
+}
+
+/// Report of benchmark of solver - entries for valid levels and aggregates of times.
+#[derive(Debug,Clone)]
+pub struct BenchReport {
+    /// Entries for all valid levels in levelset.
+    pub entries: Vec<BenchEntry>,
+    /// Minimal time of solving.
+    pub min_time: Duration,
+    /// Maximal time of solving.
+    pub max_time: Duration,
+    /// Mean time of solving.
+    pub mean_time: Duration,
+}
+
+/// Solve all valid levels from levelset and collect times, number of expanded nodes
+/// and lengths of solutions. Budget is time limit of solving single level.
+pub fn benchmark_solve(set: &LevelSet, budget: Duration) -> BenchReport {
+    let mut entries = vec![];
+    for level in set.levels().iter().filter_map(|lr| lr.as_ref().ok()) {
+        let start = Instant::now();
+        let mut core = SolverCore::new(level);
+        core.deadline = Some(start + budget);
+        let solution_len = core.search(level).map(|ni| core.moves_count(ni));
+        entries.push(BenchEntry{ name: level.name.clone(), time: start.elapsed(),
+                nodes_expanded: core.expanded, solution_len,
+                timed_out: core.timed_out });
+    }
+    let min_time = entries.iter().map(|e| e.time).min().unwrap_or_default();
+    let max_time = entries.iter().map(|e| e.time).max().unwrap_or_default();
+    let mean_time = if !entries.is_empty() {
+        entries.iter().map(|e| e.time).sum::<Duration>() / (entries.len() as u32)
+    } else { Duration::default() };
+    BenchReport{ entries, min_time, max_time, mean_time }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[test]
+    fn test_solve() {
+        let levels = vec![
+            Level::from_str("git", 8, 6,
+                " ###### \
+                 #      #\
+                 #@  ...#\
+                 #   $$$#\
+                 #      # \
+                  ###### ").unwrap(),
+            Level::from_str("first", 8, 6,
+                "   #####\
+                 ####@  #\
+                 #  $*. #\
+                 #     ##\
+                 #  #####\
+                 ####    ").unwrap(),
+            Level::from_str("funny", 6, 7,
+                "####  \
+                 # .#  \
+                 #  ###\
+                 #*@  #\
+                 #  $ #\
+                 #  ###\
+                 ####  ").unwrap(),
+            Level::from_str("blocky", 6, 7,
+                "######\
+                 #    #\
+                 # #@ #\
+                 # $* #\
+                 # .* #\
+                 #    #\
+                 ######").unwrap()];
+        for level in &levels {
+            let moves = level.solve().unwrap();
+            let mut lstate = LevelState::new(level).unwrap();
+            for m in &moves {
+                assert!(lstate.make_move(*m).0);
+            }
+            assert_eq!(&moves, lstate.moves());
+            assert!(lstate.is_done());
+            assert_eq!(Some(moves.len()), level.solution_length());
+            // A* solution is also optimal
+            let astar_moves = level.solve_astar().unwrap();
+            assert_eq!(moves.len(), astar_moves.len());
+            let lurd: String = astar_moves.iter().filter_map(|d| d.to_lurd()).collect();
+            assert!(level.verify_solution(&lurd));
+        }
+        // optimal solution
+        assert_eq!(Some(12), levels[0].solution_length());
+        
+        // unsolvable level
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #$     #\
+             #@  .. #\
+             #   $  #\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(None, level.solve());
+        assert_eq!(None, level.solution_length());
+        assert_eq!(None, level.solve_astar());
+        assert_eq!(SolveOutcome::Unsolvable, level.solve_astar_with_limit(1000000));
+        assert_eq!(SolveOutcome::LimitReached, levels[0].solve_astar_with_limit(5));
+    }
+    
+    #[test]
+    fn test_dead_squares() {
+        let level = Level::from_str("dead", 6, 5,
+            "######\
+             #@   #\
+             # $. #\
+             #    #\
+             ######").unwrap();
+        let dead = level.dead_squares();
+        assert_eq!(6*5, dead.len());
+        // corners and field between target and wall
+        assert!(dead[6 + 1]);
+        assert!(dead[3*6 + 4]);
+        assert!(dead[2*6 + 4]);
+        // target and field next to target
+        assert!(!dead[2*6 + 3]);
+        assert!(!dead[2*6 + 2]);
+        assert!(!dead[0]);
+        assert_eq!(Some(2), level.solution_length());
+        
+        // pack on dead square
+        let level = Level::from_str("dead2", 6, 5,
+            "######\
+             #$@  #\
+             #  . #\
+             #    #\
+             ######").unwrap();
+        assert!(level.dead_squares()[6 + 1]);
+        assert_eq!(SolveOutcome::Unsolvable, level.solve_bounded());
+    }
+    
+    #[test]
+    fn test_hint() {
+        let level = Level::from_str("hint", 6, 5,
+            "######\
+             #@   #\
+             # $. #\
+             #    #\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        assert_eq!(Hint::Move(Down), lstate.hint());
+        assert_eq!(Hint::LimitReached, lstate.hint_with_limit(0));
+        assert!(lstate.make_move(Down).0);
+        assert_eq!(Hint::Move(PushRight), lstate.hint());
+        assert!(lstate.make_move(Right).0);
+        assert_eq!(Hint::Done, lstate.hint());
+        assert_eq!(Hint::Done, lstate.hint_with_limit(0));
+        // pack pushed to dead square
+        let mut lstate = LevelState::new(&level).unwrap();
+        for d in [Right, Down] {
+            assert!(lstate.make_move(d).0);
+        }
+        assert_eq!(Hint::Unsolvable, lstate.hint());
+        
+        // solvable only by pushing chain of packs
+        let level = Level::from_str("chain", 8, 5,
+            "########\
+             #      #\
+             #@$$ ..#\
+             #      #\
+             ########").unwrap();
+        assert_eq!(Hint::Unsolvable, LevelState::new(&level).unwrap().hint());
+        let lstate = LevelState::new_with_rules(&level, Rules{ push_chain: 2 }).unwrap();
+        assert_eq!(Hint::Move(PushRight), lstate.hint());
+    }
+    
+    #[test]
+    fn test_solve_bounded() {
+        let level = Level::from_str("simple", 6, 3,
+            "######\
+             #@$ .#\
+             ######").unwrap();
+        assert_eq!(SolveOutcome::Solved(vec![PushRight, PushRight]), level.solve_bounded());
+        assert!(level.verify_solution("RR"));
+        assert!(!level.verify_solution("R"));
+        assert!(!level.verify_solution("rR"));
+        assert!(!level.verify_solution(""));
+        let level = Level::from_str("unsolvable", 7, 5,
+            "#######\
+             #  $  #\
+             #@    #\
+             #   . #\
+             #######").unwrap();
+        assert_eq!(SolveOutcome::Unsolvable, level.solve_bounded());
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(SolveOutcome::LimitReached, level.solve_with_limit(10));
+    }
+    
+    #[test]
+    fn test_estimate_difficulty() {
+        let level = Level::from_str("simple", 6, 3,
+            "######\
+             #@$ .#\
+             ######").unwrap();
+        let stats = level.solve_stats(1000).unwrap();
+        assert_eq!(Some(2), stats.solution_len);
+        assert_eq!(3, stats.nodes_expanded);
+        assert_eq!(2, stats.max_frontier);
+        assert_eq!(Some(Difficulty::Easy), level.estimate_difficulty());
+        let level = Level::from_str("unsolvable", 7, 5,
+            "#######\
+             #  $  #\
+             #@    #\
+             #   . #\
+             #######").unwrap();
+        assert_eq!(None, level.solve_stats(1000));
+        assert_eq!(None, level.estimate_difficulty());
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        let stats = level.solve_stats(10).unwrap();
+        assert_eq!(None, stats.solution_len);
+        assert_eq!(11, stats.nodes_expanded);
+        assert!(stats.max_frontier > 0);
+    }
+    
+    #[test]
+    fn test_is_solvable() {
+        let level = Level::from_str("simple", 6, 3,
+            "######\
+             #@$ .#\
+             ######").unwrap();
+        assert_eq!(Some(true), level.is_solvable());
+        let level = Level::from_str("unsolvable", 7, 5,
+            "#######\
+             #  $  #\
+             #@    #\
+             #   . #\
+             #######").unwrap();
+        assert_eq!(Some(false), level.is_solvable());
+        // limit reached
+        let level = Level::from_str("git", 8, 6,
+            " ###### \
+             #      #\
+             #@  ...#\
+             #   $$$#\
+             #      # \
+              ###### ").unwrap();
+        assert_eq!(None, level.is_solvable_with_limit(10));
+        assert_eq!(Some(true), level.is_solvable());
+    }
+    
+    // return lengths of solutions of level in all eight symmetries.
+    fn transformed_solution_lengths(level: &Level) -> [usize; 8] {
+        let mut lengths = [0; 8];
+        lengths[0] = level.solution_length().unwrap();
+        lengths[1] = level.mirror().solution_length().unwrap();
+        let mut tlevel = level.rotate();
+        for i in 1..4 {
+            lengths[2*i] = tlevel.solution_length().unwrap();
+            lengths[2*i+1] = tlevel.mirror().solution_length().unwrap();
+            tlevel = tlevel.rotate();
+        }
+        lengths
+    }
+    
+    #[test]
+    fn test_transformed_solve() {
+        let levels = vec![
+            Level::from_str("first", 8, 6,
+                "   #####\
+                 ####@  #\
+                 #  $*. #\
+                 #     ##\
+                 #  #####\
+                 ####    ").unwrap(),
+            Level::from_str("funny", 6, 7,
+                "####  \
+                 # .#  \
+                 #  ###\
+                 #*@  #\
+                 #  $ #\
+                 #  ###\
+                 ####  ").unwrap()];
+        for level in &levels {
+            // transforms are consistent
+            assert_eq!(*level, level.mirror().mirror());
+            assert_eq!(*level, level.rotate().rotate().rotate().rotate());
+            assert_eq!(level.rotate().mirror(), level.mirror().rotate().rotate().rotate());
+            assert_eq!(Ok(()), level.rotate().mirror().check());
+            let lengths = transformed_solution_lengths(level);
+            assert!(lengths.iter().all(|x| *x == lengths[0]));
+        }
+    }
+    
+    #[test]
+    fn test_benchmark_solve() {
+        let lset = LevelSet::from_str(r##"; Microban
+
+; Copyright: David W Skinner
+
+; funny
+####
+# .#
+#  ###
+#*@  #
+#  $ #
+#  ###
+####
+
+; bad
+####
+#.x#
+####
+
+; unsolvable
+########
+#$     #
+#@  .. #
+#   $  #
+#      #
+########
+"##).unwrap();
+        let report = benchmark_solve(&lset, Duration::from_secs(60));
+        assert_eq!(2, report.entries.len());
+        assert_eq!("funny", report.entries[0].name);
+        assert_eq!(lset.levels()[0].as_ref().unwrap().solution_length(),
+                report.entries[0].solution_len);
+        assert!(report.entries[0].nodes_expanded > 0);
+        assert_eq!("unsolvable", report.entries[1].name);
+        assert_eq!(None, report.entries[1].solution_len);
+        assert!(!report.entries[1].timed_out);
+        assert!(report.min_time <= report.mean_time);
+        assert!(report.mean_time <= report.max_time);
+        
+        let report = benchmark_solve(&lset, Duration::from_secs(0));
+        assert_eq!(2, report.entries.len());
+        // no time to expand any node
+        assert!(report.entries[0].timed_out);
+        assert_eq!(0, report.entries[0].nodes_expanded);
+        assert_eq!(None, report.entries[0].solution_len);
+    }
+}
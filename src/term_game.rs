@@ -30,7 +30,7 @@ use termion::event::Key;
 use crate::defs::*;
 
 use crate::GameResult;
-use crate::{LevelState,LevelSet};
+use crate::{Hint,LevelState,LevelSet,Progress};
 
 use Field::*;
 use Direction::*;
@@ -41,10 +41,34 @@ pub struct TermLevelSet<'a, W: Write> {
     stdout: &'a mut W,
     term_width: usize,
     term_height: usize,
+    progress: Progress,
+}
+
+const TOO_SMALL_NOTICE: &str = "Terminal too small";
+
+// write notice about too small terminal truncated to terminal width.
+fn display_too_small<W: Write>(term_width: usize, stdout: &mut W) -> io::Result<()> {
+    let len = TOO_SMALL_NOTICE.len().min(term_width);
+    write!(stdout, "{}{}", cursor::Goto(1, 1), &TOO_SMALL_NOTICE[..len])?;
+    stdout.flush()
 }
 
 fn display_message<W: Write>(term_width: usize, term_height: usize, stdout: &mut W,
                     text: &str) -> io::Result<()> {
+    draw_message(term_width, term_height, stdout, text)?;
+    
+    // wait for key.
+    if let Some(e) = std::io::stdin().keys().next() { e?; }
+    
+    Ok(())
+}
+
+// draw message in box at center of terminal.
+fn draw_message<W: Write>(term_width: usize, term_height: usize, stdout: &mut W,
+                    text: &str) -> io::Result<()> {
+    if term_width < 5 || term_height < 5 {
+        return display_too_small(term_width, stdout);
+    }
     let mut lines = vec![];
     let mut i = 0;
     let maxlen = term_width-4;
@@ -79,6 +103,9 @@ fn display_message<W: Write>(term_width: usize, term_height: usize, stdout: &mut
         }
     }
     let max_line_len = lines.iter().map(|l| l.len()).max().unwrap_or_default();
+    if max_line_len + 4 > term_width || lines.len() + 4 > term_height {
+        return display_too_small(term_width, stdout);
+    }
     let startx = (term_width - max_line_len - 4)>>1;
     let starty = (term_height - lines.len() - 4)>>1;
     
@@ -115,12 +142,27 @@ fn display_message<W: Write>(term_width: usize, term_height: usize, stdout: &mut
                     (starty+3+lines.len()+1) as u16))?;
     stdout.write(horiz_line.as_bytes())?;
     stdout.write("┘".as_bytes())?;
-    stdout.flush()?;
-    
-    // wait for key.
-    if let Some(e) = std::io::stdin().keys().next() { e?; }
-    
-    Ok(())
+    stdout.flush()
+}
+
+// return lines of level-select menu: levels in window of given height around
+// selected level. Selected level is marked by '>' and solved levels by '*'.
+// Lines are padded to same length.
+fn level_menu_lines(levelset: &LevelSet, solved: &[bool], selected: usize,
+                    height: usize) -> Vec<String> {
+    let levels = levelset.levels();
+    let top = selected.saturating_sub(height>>1).min(levels.len().saturating_sub(height));
+    let lines: Vec<String> = levels.iter().enumerate().skip(top).take(height)
+            .map(|(i, lr)| {
+        let name = match lr {
+            Ok(l) => l.name().to_string(),
+            Err(e) => format!("{} (error)", e.name),
+        };
+        format!("{}{} {:>4}. {}", if i == selected { '>' } else { ' ' },
+                if solved.get(i) == Some(&true) { '*' } else { ' ' }, i+1, name)
+    }).collect();
+    let width = lines.iter().map(|l| l.len()).max().unwrap_or_default();
+    lines.into_iter().map(|l| format!("{:<width$}", l, width=width)).collect()
 }
 
 impl<'a, W: Write> TermLevelSet<'a, W> {
@@ -129,40 +171,106 @@ impl<'a, W: Write> TermLevelSet<'a, W> {
                     levelset: &'a LevelSet) -> TermLevelSet<'a, W> {
         let (width, height) = terminal_size().unwrap();
         TermLevelSet{ levelset, stdout, term_width: width as usize,
-                term_height: height as usize }
+                term_height: height as usize, progress: Progress::new() }
     }
     
-    /// Start game in terminal.
+    /// Set progress of player. Solved levels are marked in menu and game starts
+    /// from first unsolved level.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+    
+    /// Get progress of player updated by solved levels.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+    
+    // display level-select menu.
+    fn display_menu(&mut self, selected: usize) -> io::Result<()> {
+        write!(self.stdout, "{}{}{}", Bg(Black), Fg(White), clear::All)?;
+        let height = self.term_height.saturating_sub(6).max(1);
+        let mut text = String::from("Select level (Enter - play, Q - quit):\n\n");
+        let solved: Vec<bool> = (0..self.levelset.levels().len())
+                .map(|i| self.progress.is_solved(self.levelset.name(), i)).collect();
+        text += &level_menu_lines(self.levelset, &solved, selected, height).join("\n");
+        draw_message(self.term_width, self.term_height, self.stdout, &text)
+    }
+    
+    // play level with given index. Return result of game.
+    fn play_level(&mut self, index: usize) -> io::Result<GameResult> {
+        match &self.levelset.levels()[index] {
+            Ok(level) => match LevelState::new(level) {
+                Ok(mut ls) => {
+                    let mut game = TermGame::create(self.stdout, &mut ls);
+                    let gr = game.start()?;
+                    if gr == GameResult::Solved {
+                        self.progress.set_solved(self.levelset.name(), index,
+                                game.state.steps(), game.state.pushes_count());
+                    }
+                    display_message(self.term_width, self.term_height,
+                            self.stdout, &gr.to_string())?;
+                    Ok(gr)
+                },
+                Err(err) => {
+                    display_message(self.term_width, self.term_height,
+                                self.stdout, format!("Level '{}' have errors: {}",
+                                level.name(), err).as_str())?;
+                    Ok(GameResult::Canceled)
+                }
+            },
+            Err(err) => {
+                display_message(self.term_width, self.term_height,
+                            self.stdout, &err.to_string())?;
+                Ok(GameResult::Canceled)
+            }
+        }
+    }
+    
+    /// Start game in terminal. Level is chosen from level-select menu and
+    /// after game the menu is displayed again.
     pub fn start(&mut self) -> io::Result<()> {
         write!(self.stdout, "{}{}{}{}", Bg(Black), Fg(White), clear::All,
                     cursor::Goto(1, 1))?;
         self.stdout.flush()?;
         
-        for l in self.levelset.levels() {
-            if let Ok(ref level) = l {
-                match LevelState::new(level) {
-                    Ok(mut ls) => {
-                        let gr = TermGame::create(self.stdout, &mut ls).start()?;
-                        match gr {
-                            GameResult::Solved => 
-                                { display_message(self.term_width, self.term_height,
-                                        self.stdout, "Level has been solved.")?; }
-                            GameResult::Canceled =>
-                                { display_message(self.term_width,  self.term_height,
-                                        self.stdout, "Level has been canceled.")?; }
-                            GameResult::Quit => { 
-                                    display_message(self.term_width, self.term_height,
-                                        self.stdout, "Quit.")?;
-                                    break;
-                                }
+        let levels_num = self.levelset.levels().len();
+        if levels_num == 0 {
+            write!(self.stdout, "{}{}", clear::All, cursor::Goto(1, 1))?;
+            return Ok(());
+        }
+        let page = self.term_height.saturating_sub(6).max(1);
+        // resume from first unsolved level
+        let mut selected = self.progress.first_unsolved(self.levelset.name(), levels_num)
+                .unwrap_or(0);
+        loop {
+            self.display_menu(selected)?;
+            let k = match std::io::stdin().keys().next() {
+                Some(e) => e?,
+                None => break,
+            };
+            match k {
+                Key::Up | Key::Char('k') | Key::Char('w') => {
+                    selected = selected.saturating_sub(1);
+                }
+                Key::Down | Key::Char('j') | Key::Char('s') => {
+                    selected = (selected+1).min(levels_num-1);
+                }
+                Key::PageUp => { selected = selected.saturating_sub(page); }
+                Key::PageDown => { selected = (selected+page).min(levels_num-1); }
+                Key::Home => { selected = 0; }
+                Key::End => { selected = levels_num-1; }
+                Key::Char('\n') => {
+                    match self.play_level(selected)? {
+                        GameResult::Quit => break,
+                        // select next level after solved level
+                        GameResult::Solved => {
+                            selected = (selected+1).min(levels_num-1);
                         }
-                    },
-                    Err(err) => {
-                        display_message(self.term_width, self.term_height,
-                                    self.stdout, format!("Level '{}' have errors: {}",
-                                    level.name(), err).as_str())?;
+                        GameResult::Canceled => {}
                     }
                 }
+                Key::Esc | Key::Char('q') => break,
+                _ => {}
             }
         }
         
@@ -171,50 +279,65 @@ impl<'a, W: Write> TermLevelSet<'a, W> {
     }
 }
 
-/// The game in terminal mode. Structure contains level state and some terminal utilities.
-pub struct TermGame<'a, W: Write> {
-    state: &'a mut LevelState<'a>,
+/// Rendering backend of the game. Positions of cells are positions on display.
+pub trait Renderer {
+    /// Return size of display (width and height) for level (without status bar).
+    fn size(&self) -> (usize, usize);
+    /// Clear display.
+    fn clear(&mut self) -> io::Result<()>;
+    /// Draw field in cell at display position.
+    fn draw_cell(&mut self, x: usize, y: usize, f: Field) -> io::Result<()>;
+    /// Draw status bar with name of level, number of moves (all steps including
+    /// pushes) and number of pushes.
+    fn draw_status(&mut self, name: &str, moves: usize, pushes: usize) -> io::Result<()>;
+    /// Draw notice if display is too small to draw level.
+    fn draw_too_small(&mut self) -> io::Result<()>;
+    /// Set solved state of level. Solved level can be drawn with distinct colors.
+    fn set_solved(&mut self, solved: bool);
+    /// Highlight suggested move of player in cell at display position.
+    fn draw_hint(&mut self, x: usize, y: usize, dir: Direction) -> io::Result<()>;
+    /// Present all drawn content.
+    fn present(&mut self) -> io::Result<()>;
+}
+
+/// Renderer that draws in terminal by using termion.
+pub struct TermRenderer<'a, W: Write> {
     stdout: &'a mut W,
     term_width: usize,
     term_height: usize,
     empty_line: Vec<u8>,
+    // position of cursor after last drawn cell.
+    cursor: Option<(usize, usize)>,
+    solved: bool,
 }
 
-// return start display position, start level position, displayed area size
-fn determine_display_and_level_position(leveldim: usize, dispdim: usize,
-        centered_levelpos: usize) -> (usize, usize, usize) {
-    if dispdim >= leveldim {
-        // if display dimension is greater han level dimension
-        ((dispdim>>1)-(leveldim>>1), 0, leveldim)
-    } else {
-        // if display dimension is less than level dimension
-        if centered_levelpos >= (dispdim>>1) {
-            // if position at start is non negative
-            if centered_levelpos + (dispdim-(dispdim>>1)) <= leveldim {
-                (0, centered_levelpos - (dispdim>>1), dispdim)
-            } else { // align to end of level
-                (0, leveldim-dispdim, dispdim)
-            }
-        } else { // align to zero position at start
-            (0, 0, dispdim) }
+impl<'a, W: Write> TermRenderer<'a, W> {
+    /// Create terminal renderer.
+    pub fn create(stdout: &'a mut W) -> TermRenderer<'a, W> {
+        let (width, height) = terminal_size().unwrap();
+        TermRenderer{ stdout, term_width: width as usize, term_height: height as usize,
+                empty_line: vec![b' '; width as usize], cursor: None, solved: false }
     }
 }
 
-impl<'a, W: Write> TermGame<'a, W> {
-    /// Create terminal game.
-    pub fn create(stdout: &'a mut W, ls: &'a mut LevelState<'a>) -> TermGame<'a, W> {
-        let (width, height) = terminal_size().unwrap();
-        TermGame{ state: ls, stdout, term_width: width as usize,
-                term_height: height as usize,
-                empty_line: vec![b' '; width as usize] }
+impl<'a, W: Write> Renderer for TermRenderer<'a, W> {
+    fn size(&self) -> (usize, usize) {
+        (self.term_width, self.term_height.saturating_sub(1))
     }
     
-    /// Get level state.
-    pub fn state(&'a self) -> &'a LevelState<'a> {
-        self.state
+    fn clear(&mut self) -> io::Result<()> {
+        write!(self.stdout, "{}{}", cursor::Goto(1, 1), Bg(Black))?;
+        for _ in 0..self.term_height.saturating_sub(1) {
+            self.stdout.write_all(self.empty_line.as_slice())?;
+        }
+        self.cursor = None;
+        Ok(())
     }
     
-    fn print_field(&mut self, f: Field) -> io::Result<()> {
+    fn draw_cell(&mut self, x: usize, y: usize, f: Field) -> io::Result<()> {
+        if self.cursor != Some((x, y)) {
+            write!(self.stdout, "{}", cursor::Goto((x+1) as u16, (y+1) as u16))?;
+        }
         let fmt_str: String = match f {
             Empty => " ".to_string(),
             Wall => "░".to_string(),
@@ -222,123 +345,297 @@ impl<'a, W: Write> TermGame<'a, W> {
             Pack => "▒".to_string(),
             Target => format!("{} {}", Bg(Yellow), Bg(Black)),
             PlayerOnTarget => format!("{}o{}", Bg(Yellow), Bg(Black)),
-            PackOnTarget => format!("{}▒{}", Bg(Yellow), Bg(Black)),
+            PackOnTarget => format!("{}{}▒{}{}", Bg(Yellow), Fg(Green), Fg(White),
+                    Bg(Black)),
+        };
+        if self.solved && f != Empty {
+            // solved level is drawn in green
+            write!(self.stdout, "{}{}{}", Fg(Green), fmt_str, Fg(White))?;
+        } else {
+            self.stdout.write_all(fmt_str.as_bytes())?;
+        }
+        self.cursor = Some((x+1, y));
+        Ok(())
+    }
+    
+    fn draw_status(&mut self, name: &str, moves: usize, pushes: usize) -> io::Result<()> {
+        write!(self.stdout, "{}{:<10}  Moves: {:>7}  Pushes: {:>7}",
+                cursor::Goto(1, self.term_height.max(1) as u16), name, moves, pushes)?;
+        self.cursor = None;
+        Ok(())
+    }
+    
+    fn draw_too_small(&mut self) -> io::Result<()> {
+        self.cursor = None;
+        display_too_small(self.term_width, self.stdout)
+    }
+    
+    fn set_solved(&mut self, solved: bool) {
+        self.solved = solved;
+    }
+    
+    fn draw_hint(&mut self, x: usize, y: usize, dir: Direction) -> io::Result<()> {
+        let arrow = match dir.as_move() {
+            Left => "←",
+            Right => "→",
+            Up => "↑",
+            _ => "↓",
         };
-        self.stdout.write(fmt_str.as_bytes())?;
+        write!(self.stdout, "{}{}{}{}{}{}", cursor::Goto((x+1) as u16, (y+1) as u16),
+                Bg(Blue), Fg(LightWhite), arrow, Fg(White), Bg(Black))?;
+        self.cursor = Some((x+1, y));
         Ok(())
     }
     
+    fn present(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+// view of the level on display - draws level state by using renderer.
+struct LevelView {
+    camera_margin: Option<usize>,
+    view_x: usize,
+    view_y: usize,
+}
+
+impl LevelView {
+    fn new() -> LevelView {
+        LevelView{ camera_margin: None, view_x: 0, view_y: 0 }
+    }
+    
     // cx, cy - position of level to display at center of the display.
-    fn display_level(&mut self, cx: usize, cy: usize) -> io::Result<()> {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), Bg(Black))?;
-        let levelw = self.state.level.width();
-        let levelh = self.state.level.height();
+    fn display_level<R: Renderer>(&mut self, r: &mut R, state: &LevelState,
+                    cx: usize, cy: usize) -> io::Result<()> {
+        let levelw = state.level().width();
+        let levelh = state.level().height();
         // display dimensions
-        let dispw = self.term_width;
-        let disph = self.term_height-1;
-        let (sdx, slx, fdw) = determine_display_and_level_position(levelw, dispw, cx);
-        let (sdy, sly, fdh) = determine_display_and_level_position(levelh, disph, cy);
-        
-        // fill empties
-        for _ in 0..sdy {
-            self.stdout.write(self.empty_line.as_slice())?;
+        let (dispw, disph) = r.size();
+        if dispw == 0 || disph == 0 {
+            r.clear()?;
+            return r.draw_too_small();
         }
-        for dy in sdy..sdy+fdh {
-            self.stdout.write(&self.empty_line.as_slice()[0..sdx])?;
-            for dx in sdx..sdx+fdw {
-                self.print_field(self.state.area()[(dy-sdy+sly)*levelw + slx + dx - sdx])?;
+        let (sdx, slx, fdw) = determine_display_and_level_position(levelw, dispw, cx,
+                    self.camera_margin.map(|m| (self.view_x, m)));
+        let (sdy, sly, fdh) = determine_display_and_level_position(levelh, disph, cy,
+                    self.camera_margin.map(|m| (self.view_y, m)));
+        self.view_x = slx;
+        self.view_y = sly;
+        
+        r.clear()?;
+        r.set_solved(state.is_done());
+        for dy in 0..fdh {
+            for dx in 0..fdw {
+                r.draw_cell(sdx+dx, sdy+dy, state.area()[(sly+dy)*levelw + slx+dx])?;
             }
-            self.stdout.write(&self.empty_line.as_slice()[sdx+fdw..dispw])?;
-        }
-        for _ in sdy+fdh..disph {
-            self.stdout.write(self.empty_line.as_slice())?;
         }
         // display status bar
-        self.display_statusbar()
+        self.display_statusbar(r, state)
     }
     
-    fn display_statusbar(&mut self) -> io::Result<()> {
-        // display status bar
-        write!(self.stdout, "{}{:<10}  Moves: {:>7}  Pushes: {:>7}",
-                cursor::Goto(1, (self.term_height-1+1) as u16),
-                self.state.level().name(),
-                self.state.moves().len(), self.state.pushes_count())?;
-        self.stdout.flush()?;
-        Ok(())
+    fn display_statusbar<R: Renderer>(&self, r: &mut R, state: &LevelState)
+                    -> io::Result<()> {
+        r.draw_status(state.level().name(), state.steps(), state.pushes_count())?;
+        r.present()
     }
     
-    fn display_move_fast(&mut self, player_x: usize, player_y: usize,
-                    dir: Direction) -> io::Result<()> {
-        let levelw = self.state.level.width();
-        let levelh = self.state.level.height();
-        let dispw = self.term_width;
-        let disph = self.term_height-1;
+    // redraw only fields changed by last move or undo.
+    fn display_changed_cells<R: Renderer>(&self, r: &mut R, state: &LevelState)
+                    -> io::Result<()> {
+        let levelw = state.level().width();
+        let levelh = state.level().height();
+        let (dispw, disph) = r.size();
         let scx = (dispw>>1)-(levelw>>1);
         let scy = (disph>>1)-(levelh>>1);
-        match dir {
-            Left|PushLeft|Right|PushRight => {
-                write!(self.stdout, "{}", cursor::Goto((scx+player_x-1+1) as u16,
-                    (scy+player_y+1) as u16))?;
-                self.print_field(self.state.area()[levelw*player_y + player_x-1])?;
-                self.print_field(self.state.area()[levelw*player_y + player_x])?;
-                self.print_field(self.state.area()[levelw*player_y + player_x+1])?;
-            }
-            Up|PushUp|Down|PushDown => {
-                write!(self.stdout, "{}", cursor::Goto((scx+player_x+1) as u16,
-                    (scy+player_y-1+1) as u16))?;
-                self.print_field(self.state.area()[levelw*(player_y-1) + player_x])?;
-                write!(self.stdout, "{}", cursor::Goto((scx+player_x+1) as u16,
-                    (scy+player_y+1) as u16))?;
-                self.print_field(self.state.area()[levelw*(player_y) + player_x])?;
-                write!(self.stdout, "{}", cursor::Goto((scx+player_x+1) as u16,
-                    (scy+player_y+1+1) as u16))?;
-                self.print_field(self.state.area()[levelw*(player_y+1) + player_x])?;
-            }
-            _ => {}
-        };
-        self.display_statusbar()
+        for (x, y) in state.changed_cells() {
+            r.draw_cell(scx+x, scy+y, state.area()[levelw*y + x])?;
+        }
+        self.display_statusbar(r, state)
     }
     
-    fn display_game(&mut self) -> io::Result<()> {
-        self.display_level(self.state.player_x, self.state.player_y)
+    fn display_game<R: Renderer>(&mut self, r: &mut R, state: &LevelState)
+                    -> io::Result<()> {
+        self.display_level(r, state, state.player_x, state.player_y)
     }
     
-    fn display_change(&mut self, player_x: usize, player_y: usize,
-                        dir: Direction) -> io::Result<()> {
-        let levelw = self.state.level.width();
-        let levelh = self.state.level.height();
-        let dispw = self.term_width;
-        let disph = self.term_height-1;
+    // display game and highlight suggested move at player position.
+    fn display_hint<R: Renderer>(&mut self, r: &mut R, state: &LevelState,
+                    dir: Direction) -> io::Result<()> {
+        self.display_game(r, state)?;
+        let (dispw, disph) = r.size();
+        let levelw = state.level().width();
+        let levelh = state.level().height();
+        if dispw == 0 || disph == 0 {
+            return Ok(());  // too small display
+        }
+        // player is always in view
+        let sdx = if dispw >= levelw { (dispw>>1)-(levelw>>1) } else { 0 };
+        let sdy = if disph >= levelh { (disph>>1)-(levelh>>1) } else { 0 };
+        r.draw_hint(sdx + state.player_x - self.view_x, sdy + state.player_y - self.view_y,
+                    dir)?;
+        r.present()
+    }
+    
+    fn display_change<R: Renderer>(&mut self, r: &mut R, state: &LevelState)
+                    -> io::Result<()> {
+        let levelw = state.level().width();
+        let levelh = state.level().height();
+        let (dispw, disph) = r.size();
         if levelw < dispw && levelh < disph {
-            self.display_move_fast(player_x, player_y, dir)
+            self.display_changed_cells(r, state)
         } else {
-            self.display_game()
+            self.display_game(r, state)
         }
     }
+}
+
+/// The game in terminal mode. Structure contains level state and some terminal utilities.
+pub struct TermGame<'a, W: Write> {
+    state: &'a mut LevelState<'a>,
+    renderer: TermRenderer<'a, W>,
+    view: LevelView,
+    confirm_cancel: bool,
+}
+
+// return start display position, start level position, displayed area size
+// camera - optional start level position of the current view and margin of dead zone.
+// If camera is given then view scrolls only if position is in margin of the view.
+pub(crate) fn determine_display_and_level_position(leveldim: usize, dispdim: usize,
+        centered_levelpos: usize, camera: Option<(usize, usize)>)
+        -> (usize, usize, usize) {
+    if dispdim == 0 {
+        // nothing to display
+        (0, 0, 0)
+    } else if dispdim >= leveldim {
+        // if display dimension is greater han level dimension
+        ((dispdim>>1)-(leveldim>>1), 0, leveldim)
+    } else if let Some((view_start, margin)) = camera {
+        // margin can not be greater than half of the display
+        let margin = margin.min((dispdim-1)>>1);
+        let mut start = view_start.min(leveldim-dispdim);
+        if centered_levelpos < start + margin {
+            // scroll to start
+            start = centered_levelpos.saturating_sub(margin);
+        } else if centered_levelpos + margin >= start + dispdim {
+            // scroll to end
+            start = (centered_levelpos + margin + 1 - dispdim).min(leveldim-dispdim);
+        }
+        (0, start, dispdim)
+    } else {
+        // if display dimension is less than level dimension
+        if centered_levelpos >= (dispdim>>1) {
+            // if position at start is non negative
+            if centered_levelpos + (dispdim-(dispdim>>1)) <= leveldim {
+                (0, centered_levelpos - (dispdim>>1), dispdim)
+            } else { // align to end of level
+                (0, leveldim-dispdim, dispdim)
+            }
+        } else { // align to zero position at start
+            (0, 0, dispdim) }
+    }
+}
+
+impl<'a, W: Write> TermGame<'a, W> {
+    /// Create terminal game.
+    pub fn create(stdout: &'a mut W, ls: &'a mut LevelState<'a>) -> TermGame<'a, W> {
+        TermGame{ state: ls, renderer: TermRenderer::create(stdout), view: LevelView::new(),
+                confirm_cancel: true }
+    }
+    
+    /// Set camera margin. If margin is given then the view of level is scrolled only
+    /// if player approaches to the edge of the display (to the margin).
+    /// If None then the view is always centered on the player.
+    pub fn set_camera_margin(&mut self, margin: Option<usize>) {
+        self.view.camera_margin = margin;
+    }
+    
+    /// Get camera margin.
+    pub fn camera_margin(&self) -> Option<usize> {
+        self.view.camera_margin
+    }
+    
+    /// Set confirmation of cancel. If true then cancel of level with moves must be
+    /// confirmed by pressing Escape again or Y.
+    pub fn set_confirm_cancel(&mut self, confirm: bool) {
+        self.confirm_cancel = confirm;
+    }
+    
+    /// Get confirmation of cancel.
+    pub fn confirm_cancel(&self) -> bool {
+        self.confirm_cancel
+    }
+    
+    /// Get level state.
+    pub fn state(&'a self) -> &'a LevelState<'a> {
+        self.state
+    }
+    
+    fn display_game(&mut self) -> io::Result<()> {
+        self.view.display_game(&mut self.renderer, self.state)
+    }
+    
+    fn display_change(&mut self) -> io::Result<()> {
+        self.view.display_change(&mut self.renderer, self.state)
+    }
     
     fn make_move(&mut self, d: Direction) -> io::Result<bool> {
         let (mv, _) = self.state.make_move(d);
-        if mv { self.display_change(self.state.player_x, self.state.player_y,
-                *self.state.moves().last().unwrap())?; }
+        if mv { self.display_change()?; }
         Ok(mv)
     }
     
     fn undo_move(&mut self) -> io::Result<bool> {
-        let old_player_x = self.state.player_x;
-        let old_player_y = self.state.player_y;
-        if let Some(l) = self.state.moves().last() {
-            let last_dir = *l;
-            self.state.undo_move();
-            self.display_change(old_player_x, old_player_y, last_dir)?;
+        if self.state.undo_move() {
+            self.display_change()?;
             Ok(true)
         } else { Ok(false) }
     }
     
+    fn redo_move(&mut self) -> io::Result<bool> {
+        if self.state.redo_move() {
+            self.display_change()?;
+            Ok(true)
+        } else { Ok(false) }
+    }
+    
+    fn show_hint(&mut self) -> io::Result<()> {
+        let msg = match self.state.hint() {
+            Hint::Move(dir) => {
+                return self.view.display_hint(&mut self.renderer, self.state, dir);
+            }
+            Hint::Done => "No hint.\nLevel is already solved.",
+            Hint::Unsolvable => "No hint.\n\
+                    Level can not be solved from current position.",
+            Hint::LimitReached => "No hint found within search limit.",
+        };
+        display_message(self.renderer.term_width, self.renderer.term_height,
+                self.renderer.stdout, msg)?;
+        self.display_game()
+    }
+    
+    // return true if cancel has been confirmed by Escape or Y key.
+    fn ask_cancel(&mut self) -> io::Result<bool> {
+        if !self.confirm_cancel || self.state.steps() == 0 {
+            return Ok(true);
+        }
+        draw_message(self.renderer.term_width, self.renderer.term_height,
+                self.renderer.stdout, "Cancel current level?\n\
+                Press Escape or Y to confirm.")?;
+        let confirmed = match std::io::stdin().keys().next() {
+            Some(e) => matches!(e?, Key::Esc | Key::Char('y') | Key::Char('Y')),
+            None => true,
+        };
+        if !confirmed {
+            self.display_game()?;
+        }
+        Ok(confirmed)
+    }
+    
     /// Start game in terminal.
     pub fn start(&mut self) -> io::Result<GameResult> {
-        write!(self.stdout, "{}{}{}{}", Bg(Black), Fg(White), clear::All,
+        write!(self.renderer.stdout, "{}{}{}{}", Bg(Black), Fg(White), clear::All,
                     cursor::Goto(1, 1))?;
-        self.stdout.flush()?;
+        self.renderer.stdout.flush()?;
         
         self.state.reset();
         self.display_game()?;
@@ -347,27 +644,307 @@ impl<'a, W: Write> TermGame<'a, W> {
             for e in std::io::stdin().keys() {
                 match e? {
                     Key::F(1) | Key::Char('?') => {
-                        display_message(self.term_width, self.term_height, self.stdout,
+                        display_message(self.renderer.term_width,
+                                self.renderer.term_height, self.renderer.stdout,
                                 "Keys in game:\n\
                                  Left, Right, Up, Down - move player.\n\
+                                 W, A, S, D - move player.\n\
+                                 H, J, K, L - move player (vi-style).\n\
                                  Backspace - undo move.\n\
-                                 Escape - cancel current level.\n\
+                                 R, Ctrl-R - redo move.\n\
+                                 X - restart current level.\n\
+                                 Shift-H - show hint (suggested move).\n\
+                                 Escape - cancel current level (press twice to confirm).\n\
                                  Q - Quit game.\n\
                                  F1, ? - display help.")?;
                         self.display_game()?;
                     }
-                    Key::Left => { self.make_move(Left)?; }
-                    Key::Right => { self.make_move(Right)?; }
-                    Key::Up => { self.make_move(Up)?; }
-                    Key::Down => { self.make_move(Down)?; }
+                    Key::Left | Key::Char('a') | Key::Char('h') => { self.make_move(Left)?; }
+                    Key::Right | Key::Char('d') | Key::Char('l') => { self.make_move(Right)?; }
+                    Key::Up | Key::Char('w') | Key::Char('k') => { self.make_move(Up)?; }
+                    Key::Down | Key::Char('s') | Key::Char('j') => { self.make_move(Down)?; }
                     Key::Backspace => { self.undo_move()?; }
-                    Key::Esc => { return Ok(GameResult::Canceled); }
+                    Key::Char('r') | Key::Ctrl('r') => { self.redo_move()?; }
+                    Key::Char('x') => {
+                        self.state.reset();
+                        self.display_game()?;
+                    }
+                    Key::Char('H') => { self.show_hint()?; }
+                    Key::Esc if self.ask_cancel()? => { return Ok(GameResult::Canceled); }
                     Key::Char('q') => { return Ok(GameResult::Quit); }
                     _ => {},
                 };
                 if self.state.is_done() { break; }
             }
+            // end of input before level has been solved
+            if !self.state.is_done() { return Ok(GameResult::Canceled); }
         }
+        // redraw whole solved level with distinct colors
+        self.display_game()?;
         Ok(GameResult::Solved)
     }
 }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Level;
+    
+    // renderer that records drawn cells and status.
+    struct TestRenderer {
+        width: usize,
+        height: usize,
+        cells: Vec<Option<Field>>,
+        draws: Vec<(usize, usize, Field)>,
+        status: (String, usize, usize),
+        presents: usize,
+        too_small: bool,
+        solved: bool,
+        hint: Option<(usize, usize, Direction)>,
+    }
+    
+    impl TestRenderer {
+        fn new(width: usize, height: usize) -> TestRenderer {
+            TestRenderer{ width, height, cells: vec![None; width*height], draws: vec![],
+                    status: (String::new(), 0, 0), presents: 0, too_small: false,
+                    solved: false, hint: None }
+        }
+    }
+    
+    impl Renderer for TestRenderer {
+        fn size(&self) -> (usize, usize) {
+            (self.width, self.height)
+        }
+        
+        fn clear(&mut self) -> io::Result<()> {
+            self.cells.iter_mut().for_each(|c| *c = None);
+            Ok(())
+        }
+        
+        fn draw_cell(&mut self, x: usize, y: usize, f: Field) -> io::Result<()> {
+            self.cells[y*self.width + x] = Some(f);
+            self.draws.push((x, y, f));
+            Ok(())
+        }
+        
+        fn draw_status(&mut self, name: &str, moves: usize, pushes: usize)
+                    -> io::Result<()> {
+            self.status = (name.to_string(), moves, pushes);
+            Ok(())
+        }
+        
+        fn draw_too_small(&mut self) -> io::Result<()> {
+            self.too_small = true;
+            Ok(())
+        }
+        
+        fn set_solved(&mut self, solved: bool) {
+            self.solved = solved;
+        }
+        
+        fn draw_hint(&mut self, x: usize, y: usize, dir: Direction) -> io::Result<()> {
+            self.hint = Some((x, y, dir));
+            Ok(())
+        }
+        
+        fn present(&mut self) -> io::Result<()> {
+            self.presents += 1;
+            Ok(())
+        }
+    }
+    
+    #[test]
+    fn test_renderer() {
+        let level = Level::from_str("simple", 5, 3,
+            "#####\
+             #@$.#\
+             #####").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let mut r = TestRenderer::new(10, 5);
+        let mut view = LevelView::new();
+        view.display_game(&mut r, &lstate).unwrap();
+        // level is centered at (3, 1)
+        assert_eq!(15, r.draws.len());
+        assert_eq!(None, r.cells[0]);
+        assert_eq!(Some(Wall), r.cells[10 + 3]);
+        assert_eq!(Some(Player), r.cells[20 + 4]);
+        assert_eq!(Some(Pack), r.cells[20 + 5]);
+        assert_eq!(Some(Target), r.cells[20 + 6]);
+        assert_eq!(("simple".to_string(), 0, 0), r.status);
+        assert_eq!(1, r.presents);
+        
+        r.draws.clear();
+        assert!(lstate.make_move(Right).0);
+        view.display_change(&mut r, &lstate).unwrap();
+        assert_eq!(vec![(6, 2, PackOnTarget), (4, 2, Empty), (5, 2, Player)], r.draws);
+        assert_eq!(("simple".to_string(), 1, 1), r.status);
+        assert_eq!(2, r.presents);
+        
+        r.draws.clear();
+        assert!(lstate.undo_move());
+        view.display_change(&mut r, &lstate).unwrap();
+        assert_eq!(vec![(6, 2, Target), (5, 2, Pack), (4, 2, Player)], r.draws);
+        assert_eq!(("simple".to_string(), 0, 0), r.status);
+    }
+    
+    #[test]
+    fn test_level_view_restart() {
+        let level = Level::from_str("simple", 6, 3,
+            "######\
+             #@$ .#\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let mut r = TestRenderer::new(10, 5);
+        let mut view = LevelView::new();
+        assert!(lstate.make_move(Right).0);
+        assert!(lstate.make_move(Right).0);
+        view.display_change(&mut r, &lstate).unwrap();
+        assert_eq!(("simple".to_string(), 2, 2), r.status);
+        assert!(!r.solved);
+        view.display_game(&mut r, &lstate).unwrap();
+        assert!(r.solved);
+        
+        // restart level: reset state and display whole game
+        lstate.reset();
+        view.display_game(&mut r, &lstate).unwrap();
+        assert_eq!(("simple".to_string(), 0, 0), r.status);
+        assert!(!r.solved);
+        assert_eq!(Some(Player), r.cells[20 + 3]);
+        assert_eq!(Some(Pack), r.cells[20 + 4]);
+        assert_eq!(Some(Empty), r.cells[20 + 5]);
+    }
+    
+    #[test]
+    fn test_level_view_hint() {
+        let level = Level::from_str("simple", 6, 3,
+            "######\
+             #@$ .#\
+             ######").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let mut r = TestRenderer::new(10, 5);
+        let mut view = LevelView::new();
+        let dir = PushRight;
+        assert_eq!(Hint::Move(dir), lstate.hint());
+        view.display_hint(&mut r, &lstate, dir).unwrap();
+        assert_eq!(Some((3, 2, PushRight)), r.hint);
+        assert_eq!(Some(Player), r.cells[20 + 3]);
+        
+        // scrolled view
+        assert!(lstate.make_move(Right).0);
+        let mut r = TestRenderer::new(3, 3);
+        view.display_hint(&mut r, &lstate, PushRight).unwrap();
+        assert_eq!(Some(Player), r.cells[3 + r.hint.unwrap().0]);
+        assert_eq!(Some((1, 1, PushRight)), r.hint);
+    }
+    
+    #[test]
+    fn test_level_menu_lines() {
+        let lset = LevelSet::from_str("; Menu\n\n#####\n#@$.#\n#####\n; first\n\n\
+                #####\n#@x.#\n#####\n; bad\n\n#######\n#@ $ .#\n#######\n; third\n")
+                .unwrap();
+        let solved = vec![true, false, false];
+        assert_eq!(vec![
+            " *    1. first      ".to_string(),
+            ">     2. bad (error)".to_string(),
+            "      3. third      ".to_string()],
+            level_menu_lines(&lset, &solved, 1, 10));
+        // window around selected level
+        assert_eq!(vec![
+            "      2. bad (error)".to_string(),
+            ">     3. third      ".to_string()],
+            level_menu_lines(&lset, &solved, 2, 2));
+        assert_eq!(vec![">*    1. first".to_string()],
+            level_menu_lines(&lset, &solved, 0, 1));
+    }
+    
+    #[test]
+    fn test_determine_display_and_level_position() {
+        // display greater than level - centered
+        assert_eq!((3, 0, 4), determine_display_and_level_position(4, 10, 0, None));
+        assert_eq!((3, 0, 4), determine_display_and_level_position(4, 10, 3, None));
+        assert_eq!((3, 0, 5), determine_display_and_level_position(5, 10, 2, None));
+        assert_eq!((1, 0, 5), determine_display_and_level_position(5, 6, 4, None));
+        // exact fit
+        assert_eq!((0, 0, 10), determine_display_and_level_position(10, 10, 0, None));
+        assert_eq!((0, 0, 10), determine_display_and_level_position(10, 10, 9, None));
+        assert_eq!((0, 0, 9), determine_display_and_level_position(9, 9, 4, None));
+        // player near start edge
+        assert_eq!((0, 0, 10), determine_display_and_level_position(40, 10, 0, None));
+        assert_eq!((0, 0, 10), determine_display_and_level_position(40, 10, 3, None));
+        assert_eq!((0, 0, 10), determine_display_and_level_position(40, 10, 5, None));
+        assert_eq!((0, 1, 10), determine_display_and_level_position(40, 10, 6, None));
+        assert_eq!((0, 0, 9), determine_display_and_level_position(40, 9, 4, None));
+        assert_eq!((0, 1, 9), determine_display_and_level_position(40, 9, 5, None));
+        // player in middle
+        assert_eq!((0, 15, 10), determine_display_and_level_position(40, 10, 20, None));
+        assert_eq!((0, 16, 9), determine_display_and_level_position(40, 9, 20, None));
+        // player near end edge
+        assert_eq!((0, 29, 10), determine_display_and_level_position(40, 10, 34, None));
+        assert_eq!((0, 30, 10), determine_display_and_level_position(40, 10, 35, None));
+        assert_eq!((0, 30, 10), determine_display_and_level_position(40, 10, 36, None));
+        assert_eq!((0, 30, 10), determine_display_and_level_position(40, 10, 39, None));
+        assert_eq!((0, 31, 9), determine_display_and_level_position(40, 9, 35, None));
+        assert_eq!((0, 31, 9), determine_display_and_level_position(40, 9, 39, None));
+        // level greater by one than display
+        assert_eq!((0, 0, 9), determine_display_and_level_position(10, 9, 4, None));
+        assert_eq!((0, 1, 9), determine_display_and_level_position(10, 9, 5, None));
+        assert_eq!((0, 1, 9), determine_display_and_level_position(10, 9, 9, None));
+    }
+    
+    #[test]
+    fn test_determine_display_and_level_position_camera() {
+        // player moves inside dead zone - view doesn't move
+        for pos in 13..=16 {
+            assert_eq!((0, 10, 10),
+                determine_display_and_level_position(40, 10, pos, Some((10, 3))));
+        }
+        // player enters margin - view scrolls
+        assert_eq!((0, 11, 10),
+                determine_display_and_level_position(40, 10, 17, Some((10, 3))));
+        assert_eq!((0, 9, 10),
+                determine_display_and_level_position(40, 10, 12, Some((10, 3))));
+        // clamp to level edges
+        assert_eq!((0, 0, 10),
+                determine_display_and_level_position(40, 10, 1, Some((10, 3))));
+        assert_eq!((0, 30, 10),
+                determine_display_and_level_position(40, 10, 39, Some((10, 3))));
+        // level fits on display - always centered
+        assert_eq!((3, 0, 4),
+                determine_display_and_level_position(4, 10, 2, Some((0, 3))));
+    }
+    
+    #[test]
+    fn test_too_small_terminal() {
+        let level = Level::from_str("simple", 5, 3,
+            "#####\
+             #@$.#\
+             #####").unwrap();
+        let mut lstate = LevelState::new(&level).unwrap();
+        let mut view = LevelView::new();
+        view.camera_margin = Some(2);
+        let mut r = TestRenderer::new(1, 0);
+        view.display_game(&mut r, &lstate).unwrap();
+        assert!(r.too_small);
+        assert!(r.draws.is_empty());
+        assert!(lstate.make_move(Right).0);
+        view.display_change(&mut r, &lstate).unwrap();
+        assert!(r.draws.is_empty());
+        // display 1x1
+        let mut r = TestRenderer::new(1, 1);
+        view.display_game(&mut r, &lstate).unwrap();
+        assert!(!r.too_small);
+        assert_eq!(vec![(0, 0, Player)], r.draws);
+        
+        assert_eq!((0, 0, 0), determine_display_and_level_position(5, 0, 2, Some((0, 2))));
+        assert_eq!((0, 2, 1), determine_display_and_level_position(5, 1, 2, Some((0, 2))));
+        
+        for &(w, h) in &[(1, 1), (0, 0), (4, 10), (10, 4), (8, 6)] {
+            let mut out = vec![];
+            draw_message(w, h, &mut out, "Some message that is too long.").unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.ends_with(&TOO_SMALL_NOTICE[..w.min(TOO_SMALL_NOTICE.len())]));
+        }
+        let mut out = vec![];
+        draw_message(20, 8, &mut out, "Short message").unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains(TOO_SMALL_NOTICE));
+    }
+}
@@ -0,0 +1,70 @@
+// cli.rs - tests of command line interface of sokoban
+//
+// sokoban - Sokoban game
+// Copyright (C) 2022  Mateusz Szpakowski
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; either
+// version 2.1 of the License, or (at your option) any later version.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+
+use std::process::Command;
+use sokobanlib::*;
+
+fn run_sokoban(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_sokoban"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(args).output().unwrap();
+    (output.status.code(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn test_check_valid() {
+    let (code, report) = run_sokoban(&["--check", "tests/data/valid.sok"]);
+    assert_eq!(Some(0), code);
+    assert_eq!("1: funny: OK\n2: simple: OK\nChecked 2 levels, 0 invalid\n", report);
+    let (code, report) = run_sokoban(&["--check", "--solvable", "tests/data/valid.sok"]);
+    assert_eq!(Some(0), code);
+    assert_eq!("1: funny: OK\n2: simple: OK\nChecked 2 levels, 0 invalid\n", report);
+}
+
+#[test]
+fn test_check_invalid() {
+    let (code, report) = run_sokoban(&["--check", "tests/data/sample.sok"]);
+    assert_eq!(Some(2), code);
+    assert_eq!("1: funny: OK\n\
+                2: Nr: 1, Name: bad, Error: Wrong field 2x1\n\
+                3: notarget: Too few targets - required 1.\n\
+                Checked 3 levels, 2 invalid\n", report);
+}
+
+#[test]
+fn test_check_no_file() {
+    let (code, _) = run_sokoban(&["--check"]);
+    assert_eq!(Some(1), code);
+}
+
+#[test]
+fn test_solve() {
+    let (code, report) = run_sokoban(&["--solve", "tests/data/sample.sok"]);
+    assert_eq!(Some(0), code);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[1].starts_with("2: Nr: 1, Name: bad"));
+    assert_eq!("3: notarget: unsolvable", lines[2]);
+    let lset = LevelSet::from_file(concat!(env!("CARGO_MANIFEST_DIR"),
+                "/tests/data/sample.sok")).unwrap();
+    let level = lset.levels()[0].as_ref().unwrap();
+    let solution = lines[0].strip_prefix("1: funny: ").unwrap();
+    assert!(level.verify_solution(solution));
+    assert_eq!(level.solution_length(), Some(solution.len()));
+}